@@ -0,0 +1,241 @@
+// ====================================================
+// BENCHMARKS
+// ====================================================
+// Throughput of every hash, AEAD and signature operation of the tool, to
+// compare algorithms on the actual hardware (e.g. Blake3 vs SHA-256, or
+// AES-GCM with and without AES-NI vs ChaCha20-Poly1305).
+//
+// How it works:
+// 1. Each operation is prepared once per message size (keys, ciphertexts
+//    and signatures to verify are computed outside the timed loop)
+// 2. The operation runs in batches of doubling size until the minimum
+//    measurement time is reached, so fast operations are timed over
+//    many iterations and slow ones aren't repeated needlessly
+// 3. black_box keeps the compiler from optimizing the work away
+//
+// Hashes use the streaming hashers of the registry on a single thread.
+// Only meaningful in release builds (cargo run --release bench).
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use ed25519_dalek::{Signer, SigningKey, Verifier};
+use secp256k1::{Message as Secp256k1Message, Secp256k1};
+use serde::Serialize;
+
+use crate::hash_registry::ALGORITHMS;
+use crate::session::AeadSession;
+use crate::{hash_sha256, Cipher};
+
+// Closure running the operation once on a prepared message
+type Operation<'a> = Box<dyn FnMut() + 'a>;
+
+// Builds the operation for one message (keys, ciphertexts, ...)
+type Prepare = Box<dyn for<'a> Fn(&'a [u8]) -> Operation<'a>>;
+
+pub struct Benchmark {
+    pub group: &'static str, // "hash", "aead" or "signature"
+    pub name: String,
+    pub operation: &'static str,
+    prepare: Prepare,
+}
+
+pub struct BenchResult {
+    pub size: usize,
+    pub iterations: u64,
+    pub elapsed: Duration,
+}
+
+// One object of the `bench --json` output
+#[derive(Serialize)]
+pub struct JsonResult<'a> {
+    group: &'a str,
+    algorithm: &'a str,
+    operation: &'a str,
+    size: usize,
+    iterations: u64,
+    seconds: f64,
+    mb_per_s: f64,
+    ops_per_s: f64,
+}
+
+impl BenchResult {
+    pub fn megabytes_per_second(&self) -> f64 {
+        (self.size as f64 * self.iterations as f64) / self.elapsed.as_secs_f64() / 1e6
+    }
+
+    pub fn operations_per_second(&self) -> f64 {
+        self.iterations as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl Benchmark {
+    pub fn json_result(&self, result: &BenchResult) -> JsonResult<'_> {
+        JsonResult {
+            group: self.group,
+            algorithm: &self.name,
+            operation: self.operation,
+            size: result.size,
+            iterations: result.iterations,
+            seconds: result.elapsed.as_secs_f64(),
+            mb_per_s: result.megabytes_per_second(),
+            ops_per_s: result.operations_per_second(),
+        }
+    }
+
+    // Runs the operation on `message` for at least `min_time`
+    pub fn measure(&self, message: &[u8], min_time: Duration) -> BenchResult {
+        let mut operation = (self.prepare)(message);
+        operation(); // Warm-up (caches, lazy initialization)
+
+        let mut iterations = 0;
+        let mut batch = 1;
+        let start = Instant::now();
+        loop {
+            for _ in 0..batch {
+                operation();
+            }
+            iterations += batch;
+            if start.elapsed() >= min_time {
+                break;
+            }
+            batch *= 2;
+        }
+
+        BenchResult { size: message.len(), iterations, elapsed: start.elapsed() }
+    }
+}
+
+// Every benchmarked operation
+pub fn benchmarks() -> Vec<Benchmark> {
+    let mut benchmarks = Vec::new();
+
+    for algorithm in ALGORITHMS {
+        benchmarks.push(Benchmark {
+            group: "hash",
+            name: algorithm.name.to_string(),
+            operation: "hash",
+            prepare: Box::new(move |message| Box::new(move || {
+                let mut hasher = algorithm.hasher();
+                hasher.update(black_box(message));
+                black_box(hasher.finalize());
+            })),
+        });
+    }
+
+    // Fixed key and nonce: the ciphertexts are thrown away
+    const KEY: [u8; 32] = [7u8; 32];
+    const NONCE: [u8; 12] = [9u8; 12];
+    for cipher in [Cipher::AesGcm, Cipher::ChaCha20Poly1305] {
+        benchmarks.push(Benchmark {
+            group: "aead",
+            name: cipher.name().to_string(),
+            operation: "encrypt",
+            prepare: Box::new(move |message| {
+                let session = AeadSession::new(cipher, &KEY).expect("32-byte key");
+                Box::new(move || {
+                    let _ = black_box(session.encrypt(black_box(message), &NONCE));
+                })
+            }),
+        });
+        // Same encryption with the key schedule redone for every message, as
+        // encrypt_aes_gcm does: the difference is what a session saves
+        benchmarks.push(Benchmark {
+            group: "aead",
+            name: cipher.name().to_string(),
+            operation: "encrypt-per-call",
+            prepare: Box::new(move |message| Box::new(move || {
+                black_box(cipher.encrypt(black_box(message), &KEY, &NONCE));
+            })),
+        });
+        benchmarks.push(Benchmark {
+            group: "aead",
+            name: cipher.name().to_string(),
+            operation: "decrypt",
+            prepare: Box::new(move |message| {
+                let session = AeadSession::new(cipher, &KEY).expect("32-byte key");
+                let ciphertext = session.encrypt(message, &NONCE).expect("encryption");
+                Box::new(move || {
+                    let _ = black_box(session.decrypt(black_box(&ciphertext), &NONCE));
+                })
+            }),
+        });
+    }
+
+    // Ed25519 hashes the whole message (SHA-512) as part of signing
+    benchmarks.push(Benchmark {
+        group: "signature",
+        name: "Ed25519".to_string(),
+        operation: "sign",
+        prepare: Box::new(|message| {
+            let signing_key = SigningKey::from_bytes(&[1u8; 32]);
+            Box::new(move || {
+                black_box(signing_key.sign(black_box(message)));
+            })
+        }),
+    });
+    benchmarks.push(Benchmark {
+        group: "signature",
+        name: "Ed25519".to_string(),
+        operation: "verify",
+        prepare: Box::new(|message| {
+            let signing_key = SigningKey::from_bytes(&[1u8; 32]);
+            let verifying_key = signing_key.verifying_key();
+            let signature = signing_key.sign(message);
+            Box::new(move || {
+                let _ = black_box(verifying_key.verify(black_box(message), &signature));
+            })
+        }),
+    });
+
+    // Secp256k1 signs the SHA-256 of the message, as in secp256k1_example
+    benchmarks.push(Benchmark {
+        group: "signature",
+        name: "Secp256k1".to_string(),
+        operation: "sign",
+        prepare: Box::new(|message| {
+            let secp = Secp256k1::new();
+            let secret_key = secp256k1::SecretKey::from_slice(&[42u8; 32]).expect("valid secret key");
+            Box::new(move || {
+                let digest = Secp256k1Message::from_digest_slice(&hash_sha256(black_box(message))).expect("32-byte digest");
+                black_box(secp.sign_ecdsa(&digest, &secret_key));
+            })
+        }),
+    });
+    benchmarks.push(Benchmark {
+        group: "signature",
+        name: "Secp256k1".to_string(),
+        operation: "verify",
+        prepare: Box::new(|message| {
+            let secp = Secp256k1::new();
+            let secret_key = secp256k1::SecretKey::from_slice(&[42u8; 32]).expect("valid secret key");
+            let public_key = secp256k1::PublicKey::from_secret_key(&secp, &secret_key);
+            let digest = Secp256k1Message::from_digest_slice(&hash_sha256(message)).expect("32-byte digest");
+            let signature = secp.sign_ecdsa(&digest, &secret_key);
+            Box::new(move || {
+                let digest = Secp256k1Message::from_digest_slice(&hash_sha256(black_box(message))).expect("32-byte digest");
+                let _ = black_box(secp.verify_ecdsa(&digest, &signature, &public_key));
+            })
+        }),
+    });
+
+    benchmarks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_output_is_valid() {
+        let benchmark = benchmarks().into_iter().find(|benchmark| benchmark.name == "blake3").unwrap();
+        let result = benchmark.measure(&[0x5a; 64], Duration::from_millis(1));
+        let json: serde_json::Value = serde_json::from_str(&serde_json::to_string(&benchmark.json_result(&result)).unwrap()).unwrap();
+
+        assert_eq!(json["group"], "hash");
+        assert_eq!(json["algorithm"], "blake3");
+        assert_eq!(json["size"], 64);
+        assert_eq!(json["iterations"], result.iterations);
+        assert!(json["mb_per_s"].as_f64().unwrap() > 0.0);
+    }
+}
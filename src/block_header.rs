@@ -0,0 +1,122 @@
+// ====================================================
+// BITCOIN BLOCK HEADERS
+// ====================================================
+// Every Bitcoin block starts with an 80-byte header, the only part that
+// is hashed for the proof of work and that links blocks into a chain:
+//
+//     version          4 bytes  little-endian
+//     previous block  32 bytes  hash of the parent block's header
+//     Merkle root     32 bytes  commitment to all the transactions
+//     time             4 bytes  little-endian Unix timestamp
+//     nBits            4 bytes  little-endian compact target
+//     nonce            4 bytes  little-endian, changed by miners
+//
+// The block hash is the double SHA-256 of the header. Hashes are stored in
+// internal byte order but displayed reversed, as in block explorers.
+
+use std::error::Error;
+
+use crate::hash_double_sha256;
+use crate::merkle::Hash256;
+use crate::pow::{self, HEADER_LEN};
+
+pub struct BlockHeader {
+    pub version: i32,
+    pub prev_block_hash: Hash256, // Internal byte order
+    pub merkle_root: Hash256,     // Internal byte order
+    pub time: u32,
+    pub bits: u32,
+    pub nonce: u32,
+}
+
+impl BlockHeader {
+    pub fn parse(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let bytes: &[u8; HEADER_LEN] = bytes
+            .try_into()
+            .map_err(|_| format!("block header must be {} bytes, got {}", HEADER_LEN, bytes.len()))?;
+        let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().expect("4 bytes"));
+
+        Ok(BlockHeader {
+            version: u32_at(0) as i32,
+            prev_block_hash: bytes[4..36].try_into().expect("32 bytes"),
+            merkle_root: bytes[36..68].try_into().expect("32 bytes"),
+            time: u32_at(68),
+            bits: u32_at(72),
+            nonce: u32_at(76),
+        })
+    }
+
+    pub fn serialize(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0u8; HEADER_LEN];
+        bytes[..4].copy_from_slice(&self.version.to_le_bytes());
+        bytes[4..36].copy_from_slice(&self.prev_block_hash);
+        bytes[36..68].copy_from_slice(&self.merkle_root);
+        bytes[68..72].copy_from_slice(&self.time.to_le_bytes());
+        bytes[72..76].copy_from_slice(&self.bits.to_le_bytes());
+        bytes[76..].copy_from_slice(&self.nonce.to_le_bytes());
+        bytes
+    }
+
+    // Double SHA-256 of the header, internal byte order
+    pub fn block_hash(&self) -> Hash256 {
+        hash_double_sha256(&self.serialize()).try_into().expect("SHA-256 output is 32 bytes")
+    }
+
+    // Big-endian target decoded from nBits
+    pub fn target(&self) -> Result<[u8; 32], Box<dyn Error>> {
+        pow::target_from_bits(self.bits)
+    }
+
+    // Whether the block hash is at or below the target
+    pub fn check_proof_of_work(&self) -> Result<bool, Box<dyn Error>> {
+        Ok(pow::meets_target(&self.block_hash(), &self.target()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::hash_to_hex;
+
+    const GENESIS_HEADER: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+
+    // Block 125552, the example of the Bitcoin wiki "Block hashing algorithm" page
+    const BLOCK_125552_HEADER: &str = "0100000081cd02ab7e569e8bcd9317e2fe99f2de44d49ab2b8851ba4a308000000000000e320b6c2fffc8d750423db8b1eb942ae710e951ed797f7affc8892b0f1fc122bc7f5d74df2b9441a42a14695";
+
+    #[test]
+    fn genesis_block() {
+        let bytes = hex::decode(GENESIS_HEADER).unwrap();
+        let header = BlockHeader::parse(&bytes).unwrap();
+
+        assert_eq!(header.version, 1);
+        assert_eq!(header.prev_block_hash, [0u8; 32]);
+        assert_eq!(hash_to_hex(&header.merkle_root), "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b");
+        assert_eq!(header.time, 1231006505); // 2009-01-03 18:15:05 UTC
+        assert_eq!(header.bits, 0x1d00ffff);
+        assert_eq!(header.nonce, 2083236893);
+        assert_eq!(header.serialize().as_slice(), bytes.as_slice());
+
+        assert_eq!(hash_to_hex(&header.block_hash()), "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f");
+        assert_eq!(hex::encode(header.target().unwrap()), "00000000ffff0000000000000000000000000000000000000000000000000000");
+        assert!(header.check_proof_of_work().unwrap());
+    }
+
+    #[test]
+    fn block_125552() {
+        let header = BlockHeader::parse(&hex::decode(BLOCK_125552_HEADER).unwrap()).unwrap();
+        assert_eq!(hash_to_hex(&header.prev_block_hash), "00000000000008a3a41b85b8b29ad444def299fee21793cd8b9e567eab02cd81");
+        assert_eq!(header.bits, 0x1a44b9f2);
+        assert_eq!(hash_to_hex(&header.block_hash()), "00000000000000001e8d6829a8a21adc5d38d0a473b144b6765798e61f98bd1d");
+        assert!(header.check_proof_of_work().unwrap());
+    }
+
+    #[test]
+    fn invalid_proof_of_work() {
+        let mut header = BlockHeader::parse(&hex::decode(GENESIS_HEADER).unwrap()).unwrap();
+        header.nonce += 1;
+        assert!(!header.check_proof_of_work().unwrap());
+
+        let error = BlockHeader::parse(&[0u8; 79]).err().unwrap();
+        assert_eq!(error.to_string(), "block header must be 80 bytes, got 79");
+    }
+}
@@ -0,0 +1,317 @@
+// ====================================================
+// CHECKSUM FILES (sha256sum / b2sum / b3sum format)
+// ====================================================
+// A checksum file lists one digest per file, so a whole directory can be
+// verified later (downloads, backups, releases):
+//
+//     <hex digest>  <path>      text mode (what the tools write)
+//     <hex digest> *<path>      binary mode (same digest, written on Windows)
+//     SHA256 (<path>) = <hex>   BSD style, written with --tag
+//
+// Paths containing a backslash or a newline are escaped ("\\" and "\n") and
+// the line then starts with a backslash, as GNU coreutils does.
+//
+// Checking re-hashes every listed file and prints "<path>: OK" or
+// "<path>: FAILED"; the caller turns any failure into a nonzero exit code.
+// BSD lines name their algorithm, the other formats are checked with the
+// algorithm given by the caller. A digest whose length doesn't match its
+// algorithm makes the line improperly formatted.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::hash_registry::HashAlgorithm;
+
+// One "<digest> <path>" entry of a checksum file
+#[derive(Debug, PartialEq, Eq)]
+pub struct ChecksumLine {
+    pub algorithm: Option<String>, // BSD style only: "SHA256", "BLAKE2b", ...
+    pub digest: Vec<u8>,
+    pub path: String,
+}
+
+// Formats a line the way sha256sum/b2sum/b3sum print it
+pub fn format_line(digest: &[u8], path: &str) -> String {
+    if path.contains(['\\', '\n']) {
+        format!("\\{}  {}", hex::encode(digest), escape_path(path))
+    } else {
+        format!("{}  {}", hex::encode(digest), path)
+    }
+}
+
+// Keeps a path on a single line: "\\" for a backslash, "\n" for a newline
+pub fn escape_path(path: &str) -> String {
+    path.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+pub fn unescape_path(path: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next()? {
+                '\\' => unescaped.push('\\'),
+                'n' => unescaped.push('\n'),
+                _ => return None,
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    Some(unescaped)
+}
+
+// Parses any of the three line formats. None for malformed lines.
+pub fn parse_line(line: &str) -> Option<ChecksumLine> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+
+    let (algorithm, digest, path) = if let Some((tag, digest)) = line.rsplit_once(") = ") {
+        // BSD style: "NAME (path) = digest"
+        let (algorithm, path) = tag.split_once(" (")?;
+        (Some(algorithm.to_string()), digest, path)
+    } else {
+        // GNU style: "digest  path" or "digest *path"
+        let (digest, path) = line.split_once(' ')?;
+        let path = path.strip_prefix([' ', '*'])?;
+        (None, digest, path)
+    };
+
+    let path = if escaped { unescape_path(path)? } else { path.to_string() };
+    if path.is_empty() {
+        return None;
+    }
+    Some(ChecksumLine { algorithm, digest: hex::decode(digest).ok()?, path })
+}
+
+// Registered algorithm for a BSD tag: "SHA256" (sha256sum), "SHA3-256",
+// "BLAKE2b" (b2sum), "BLAKE3", ...
+pub fn bsd_algorithm(tag: &str) -> Option<&'static HashAlgorithm> {
+    let name = tag.to_ascii_lowercase();
+    let name = match name.as_str() {
+        "blake2b-512" => "blake2b",
+        "blake2s-256" => "blake2s",
+        name => name,
+    };
+    HashAlgorithm::find(name)
+}
+
+// Every regular file under a directory, in a stable (sorted) order so the
+// same tree always produces the same checksum file. Symlinks to files are
+// listed, symlinks to directories are not followed (they could form a loop).
+pub fn collect_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    collect_dir(path, &mut files)?;
+    Ok(files)
+}
+
+fn collect_dir(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<_>>()?;
+    entries.sort();
+
+    for entry in entries {
+        let metadata = fs::symlink_metadata(&entry)?;
+        if metadata.is_dir() {
+            collect_dir(&entry, files)?;
+        } else if metadata.is_file() || (metadata.is_symlink() && entry.is_file()) {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+// Outcome of checking a checksum file
+#[derive(Default)]
+pub struct CheckSummary {
+    pub checked: usize,    // Lines whose file was hashed
+    pub failed: usize,     // Digest mismatch
+    pub unreadable: usize, // Missing or unreadable file
+    pub malformed: usize,  // Lines that couldn't be parsed
+}
+
+impl CheckSummary {
+    // Like GNU coreutils, malformed lines are only warned about, unless
+    // no line at all could be parsed
+    pub fn is_ok(&self) -> bool {
+        self.checked > 0 && self.failed == 0 && self.unreadable == 0
+    }
+
+    // Same warnings as GNU coreutils
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.checked == 0 {
+            warnings.push("no properly formatted checksum lines found".to_string());
+        }
+        match self.malformed {
+            0 => {},
+            1 => warnings.push("1 line is improperly formatted".to_string()),
+            n => warnings.push(format!("{} lines are improperly formatted", n)),
+        }
+        match self.unreadable {
+            0 => {},
+            1 => warnings.push("1 listed file could not be read".to_string()),
+            n => warnings.push(format!("{} listed files could not be read", n)),
+        }
+        match self.failed {
+            0 => {},
+            1 => warnings.push("1 computed checksum did NOT match".to_string()),
+            n => warnings.push(format!("{} computed checksums did NOT match", n)),
+        }
+        warnings
+    }
+}
+
+// Verifies every line of a checksum file and prints one "<path>: OK/FAILED"
+// line per file. BSD lines are hashed with the algorithm they name, the
+// others with `algorithm`.
+pub fn check(
+    contents: &str,
+    algorithm: &'static HashAlgorithm,
+    mut hash_file: impl FnMut(&'static HashAlgorithm, &Path) -> io::Result<Vec<u8>>,
+) -> CheckSummary {
+    let mut summary = CheckSummary::default();
+
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        let Some(entry) = parse_line(line) else {
+            summary.malformed += 1;
+            continue;
+        };
+        let line_algorithm = match &entry.algorithm {
+            Some(tag) => bsd_algorithm(tag),
+            None => Some(algorithm),
+        };
+        let Some(line_algorithm) = line_algorithm.filter(|a| a.digest_size() == entry.digest.len()) else {
+            summary.malformed += 1;
+            continue;
+        };
+
+        summary.checked += 1;
+        match hash_file(line_algorithm, Path::new(&entry.path)) {
+            Ok(digest) if digest == entry.digest => println!("{}: OK", entry.path),
+            Ok(_) => {
+                println!("{}: FAILED", entry.path);
+                summary.failed += 1;
+            },
+            Err(e) => {
+                eprintln!("{}: {}", entry.path, e);
+                println!("{}: FAILED open or read", entry.path);
+                summary.unreadable += 1;
+            },
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    fn line(algorithm: Option<&str>, digest: &str, path: &str) -> Option<ChecksumLine> {
+        Some(ChecksumLine { algorithm: algorithm.map(String::from), digest: hex::decode(digest).unwrap(), path: path.to_string() })
+    }
+
+    #[test]
+    fn parse_formats() {
+        // GNU text and binary mode
+        assert_eq!(parse_line(&format!("{}  a b.txt", ABC_SHA256)), line(None, ABC_SHA256, "a b.txt"));
+        assert_eq!(parse_line(&format!("{} *a b.txt", ABC_SHA256)), line(None, ABC_SHA256, "a b.txt"));
+        // BSD style, the path may contain ") = "
+        assert_eq!(parse_line(&format!("SHA256 (a (1).txt) = {}", ABC_SHA256)), line(Some("SHA256"), ABC_SHA256, "a (1).txt"));
+        assert_eq!(parse_line(&format!("SHA256 (x) = y) = {}", ABC_SHA256)), line(Some("SHA256"), ABC_SHA256, "x) = y"));
+        // Escaped paths
+        assert_eq!(parse_line(&format!("\\{}  dir\\\\new\\nline", ABC_SHA256)), line(None, ABC_SHA256, "dir\\new\nline"));
+        assert_eq!(parse_line(&format_line(&hex::decode(ABC_SHA256).unwrap(), "a\nb")), line(None, ABC_SHA256, "a\nb"));
+
+        assert_eq!(parse_line(ABC_SHA256), None);
+        assert_eq!(parse_line(&format!("{} a.txt", ABC_SHA256)), None);
+        assert_eq!(parse_line(&format!("{}  ", ABC_SHA256)), None);
+        assert_eq!(parse_line("xyz  a.txt"), None);
+        assert_eq!(parse_line(&format!("\\{}  bad\\escape", ABC_SHA256)), None);
+    }
+
+    #[test]
+    fn bsd_tags() {
+        assert_eq!(bsd_algorithm("SHA256").unwrap().name, "sha256");
+        assert_eq!(bsd_algorithm("SHA3-512").unwrap().name, "sha3-512");
+        assert_eq!(bsd_algorithm("BLAKE2b").unwrap().name, "blake2b");
+        assert_eq!(bsd_algorithm("BLAKE3").unwrap().name, "blake3");
+        assert!(bsd_algorithm("MD5").is_none());
+    }
+
+    #[test]
+    fn check_directory() {
+        let dir = std::env::temp_dir().join(format!("rust-cryptocraphy-checksum-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("abc.txt"), b"abc").unwrap();
+        fs::write(dir.join("sub/empty"), b"").unwrap();
+        #[cfg(unix)]
+        {
+            // A symlink loop and a symlink to a file
+            std::os::unix::fs::symlink(&dir, dir.join("sub/loop")).unwrap();
+            std::os::unix::fs::symlink(dir.join("abc.txt"), dir.join("link.txt")).unwrap();
+        }
+
+        let files = collect_files(&dir).unwrap();
+        let mut expected = vec![dir.join("abc.txt"), dir.join("sub/empty")];
+        if cfg!(unix) {
+            expected.insert(1, dir.join("link.txt"));
+        }
+        assert_eq!(files, expected);
+
+        let sha256 = HashAlgorithm::find("sha256").unwrap();
+        let blake3 = HashAlgorithm::find("blake3").unwrap();
+        let hash_file = |algorithm: &HashAlgorithm, path: &Path| -> io::Result<Vec<u8>> {
+            crate::hash_registry::stream_reader(algorithm.hasher(), fs::File::open(path)?)
+        };
+        let abc = dir.join("abc.txt").display().to_string();
+        let empty = dir.join("sub/empty").display().to_string();
+        let empty_blake3 = hex::encode(crate::hash_blake3(b""));
+
+        // GNU lines use the given algorithm, BSD lines the one they name
+        let sums = format!(
+            "{}  {}\n{}  {}\nBLAKE3 ({}) = {}\n",
+            ABC_SHA256,
+            abc,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            empty,
+            empty,
+            empty_blake3
+        );
+        let summary = check(&sums, sha256, hash_file);
+        assert_eq!((summary.checked, summary.failed, summary.unreadable, summary.malformed), (3, 0, 0, 0));
+        assert!(summary.is_ok());
+
+        // Checked with blake3, the SHA-256 digests are wrong but well formed
+        let summary = check(&sums, blake3, hash_file);
+        assert_eq!((summary.checked, summary.failed), (3, 2));
+
+        // Wrong digest lengths and unknown algorithms are improperly formatted,
+        // not failures; a missing file is unreadable
+        let sums = format!(
+            "{}  {}\nSHA512 ({}) = {}\nMD5 ({}) = 900150983cd24fb0d6963f7d28e17f72\n{}  {}\n",
+            &ABC_SHA256[..62],
+            abc,
+            abc,
+            ABC_SHA256,
+            abc,
+            ABC_SHA256,
+            dir.join("missing").display()
+        );
+        let summary = check(&sums, sha256, hash_file);
+        assert_eq!((summary.checked, summary.failed, summary.unreadable, summary.malformed), (1, 0, 1, 3));
+        assert!(!summary.is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,490 @@
+// ====================================================
+// CONTENT-ADDRESSED CHUNK STORE
+// ====================================================
+// Backup deduplication: files are cut into chunks with FastCDC (fastcdc.rs)
+// and each chunk is stored once, under its Blake3 hash (its "chunk id").
+// Backing up a file again, or a slightly edited copy, only stores the
+// chunks that changed. A chunk is checked against its id when read back.
+//
+// Layout of the store directory:
+//   config                 average chunk size and encryption, set on creation
+//   objects/ab/cdef...     one file per chunk, named by its hex id
+//   files/NAME             recipe of a stored file: "<chunk id> <length>" per line
+//
+// Encrypted stores (created with a 32-byte key):
+// - Chunks are encrypted with ChaCha20-Poly1305 and a random nonce, stored
+//   as nonce || ciphertext. The chunk id is the associated data, so an
+//   object moved to another id fails to decrypt.
+// - Chunk ids are keyed Blake3 hashes: plain hashes would let anyone with
+//   the store check whether it holds a chunk of a known file.
+// - The encryption and id keys are derived from the store key with Blake3
+//   derive_key. The config holds a key check value to refuse a wrong key.
+// - Recipes only hold ids and lengths: list, rm and gc don't need the key.
+//   File names and sizes are not hidden.
+//
+// gc deletes the objects no recipe refers to. It must not run while
+// another process is putting files into the store.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Read;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use rand::RngCore;
+
+use crate::fastcdc::{self, ChunkerConfig};
+use crate::nonce_counter::sync_parent_dir;
+use crate::session::AeadSession;
+use crate::Cipher;
+
+pub type ChunkId = [u8; 32];
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+// Blake3 derive_key contexts
+const ENCRYPTION_KEY_CONTEXT: &str = "rust-cryptocraphy chunk store 2025 chunk encryption key";
+const ID_KEY_CONTEXT: &str = "rust-cryptocraphy chunk store 2025 chunk id key";
+const KEY_CHECK_CONTEXT: &str = "rust-cryptocraphy chunk store 2025 key check";
+
+// Keys of an encrypted store
+struct StoreKeys {
+    session: AeadSession,
+    id_key: Vec<u8>,
+}
+
+// A stored file, as listed
+pub struct StoredFile {
+    pub name: String,
+    pub size: u64,
+    pub chunks: usize,
+}
+
+pub struct PutReport {
+    pub size: u64,
+    pub chunks: usize,
+    pub new_chunks: usize,
+    pub new_bytes: u64, // Bytes written to objects/, encryption overhead included
+}
+
+pub struct GcReport {
+    pub kept: usize,
+    pub removed: usize,
+    pub freed_bytes: u64,
+}
+
+struct ObjectFile {
+    path: PathBuf,
+    id: Option<ChunkId>, // None for a name that isn't an id (leftover temporary file)
+    size: u64,
+}
+
+pub struct ChunkStore {
+    dir: PathBuf,
+    chunker: ChunkerConfig,
+    encrypted: bool,
+    keys: Option<StoreKeys>, // None for plain stores, or when opened without the key
+}
+
+fn key_check(key: &[u8]) -> String {
+    hex::encode(crate::derive_key_blake3(KEY_CHECK_CONTEXT, key, 16))
+}
+
+// Recipes are files of the store directory: no paths, no hidden files
+fn check_name(name: &str) -> Result<(), Box<dyn Error>> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(format!("invalid file name in the store: {:?}", name).into());
+    }
+    Ok(())
+}
+
+// Writes to a temporary file, syncs it, then renames it: no file is ever
+// half-written, even after a crash. Each writer has its own temporary file
+// (process id and random suffix), so concurrent puts of the same chunk can't
+// mix their bytes. Temporary files are hidden: they can't be mistaken for a
+// recipe or an object.
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+    let file_name = path.file_name().expect("store paths end with a file name").to_string_lossy();
+    let mut suffix = [0u8; 8];
+    rand::rng().fill_bytes(&mut suffix);
+    let tmp_path = path.with_file_name(format!(".{}.{}-{}.tmp", file_name, std::process::id(), hex::encode(suffix)));
+
+    let result = (|| -> Result<(), Box<dyn Error>> {
+        let mut tmp_file = OpenOptions::new().write(true).create_new(true).open(&tmp_path)?;
+        tmp_file.write_all(contents)?;
+        tmp_file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        sync_parent_dir(path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+impl ChunkStore {
+    // Creates a store in a new or empty directory, encrypted if a key is given
+    pub fn create(dir: &Path, chunker: ChunkerConfig, key: Option<&[u8]>) -> Result<Self, Box<dyn Error>> {
+        if dir.join("config").exists() {
+            return Err(format!("{}: a store already exists", dir.display()).into());
+        }
+        let encryption = match key {
+            Some(key) if key.len() != KEY_LEN => return Err(format!("store key must be {} bytes, got {}", KEY_LEN, key.len()).into()),
+            Some(key) => format!("chacha20-poly1305 {}", key_check(key)),
+            None => "none".to_string(),
+        };
+
+        fs::create_dir_all(dir.join("objects"))?;
+        fs::create_dir_all(dir.join("files"))?;
+        sync_parent_dir(&dir.join("objects"))?;
+        write_atomic(&dir.join("config"), format!("avg-size {}\nencryption {}\n", chunker.avg_size(), encryption).as_bytes())?;
+        Self::open(dir, key)
+    }
+
+    // Opens an existing store. Encrypted stores need their key to put and get files.
+    pub fn open(dir: &Path, key: Option<&[u8]>) -> Result<Self, Box<dyn Error>> {
+        let config = fs::read_to_string(dir.join("config")).map_err(|e| format!("{}: not a chunk store ({})", dir.display(), e))?;
+        let mut avg_size = None;
+        let mut check = None;
+        for line in config.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["avg-size", size] => avg_size = Some(size.parse()?),
+                ["encryption", "none"] => check = Some(None),
+                ["encryption", "chacha20-poly1305", value] => check = Some(Some(value.to_string())),
+                [] => continue,
+                _ => return Err(format!("malformed store config line: {:?}", line).into()),
+            }
+        }
+        let chunker = ChunkerConfig::new(avg_size.ok_or("store config has no avg-size")?)?;
+        let check = check.ok_or("store config has no encryption line")?;
+
+        let keys = match (&check, key) {
+            (None, Some(_)) => return Err("this store isn't encrypted, no key is needed".into()),
+            (Some(check), Some(key)) => {
+                if key.len() != KEY_LEN || key_check(key) != *check {
+                    return Err("wrong key for this store".into());
+                }
+                Some(StoreKeys {
+                    session: AeadSession::new(Cipher::ChaCha20Poly1305, &crate::derive_key_blake3(ENCRYPTION_KEY_CONTEXT, key, KEY_LEN))?,
+                    id_key: crate::derive_key_blake3(ID_KEY_CONTEXT, key, KEY_LEN),
+                })
+            },
+            (_, None) => None,
+        };
+
+        Ok(ChunkStore { dir: dir.to_path_buf(), chunker, encrypted: check.is_some(), keys })
+    }
+
+    pub fn chunker(&self) -> ChunkerConfig {
+        self.chunker
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    // The keys of an encrypted store, None for a plain one
+    fn keys(&self) -> Result<Option<&StoreKeys>, Box<dyn Error>> {
+        match (self.encrypted, &self.keys) {
+            (true, None) => Err("this store is encrypted, its key is required".into()),
+            (_, keys) => Ok(keys.as_ref()),
+        }
+    }
+
+    fn chunk_id(&self, chunk: &[u8]) -> Result<ChunkId, Box<dyn Error>> {
+        let id = match self.keys()? {
+            Some(keys) => crate::hash_blake3_keyed(chunk, &keys.id_key, 32, 0)?,
+            None => crate::hash_blake3(chunk),
+        };
+        Ok(id.try_into().expect("chunk ids are 32 bytes"))
+    }
+
+    fn object_path(&self, id: &ChunkId) -> PathBuf {
+        let hex = hex::encode(id);
+        self.dir.join("objects").join(&hex[..2]).join(&hex[2..])
+    }
+
+    fn recipe_path(&self, name: &str) -> Result<PathBuf, Box<dyn Error>> {
+        check_name(name)?;
+        Ok(self.dir.join("files").join(name))
+    }
+
+    // Stores a chunk unless it's already there. Returns its id and the bytes written.
+    fn put_chunk(&self, chunk: &[u8]) -> Result<(ChunkId, u64), Box<dyn Error>> {
+        let id = self.chunk_id(chunk)?;
+        let path = self.object_path(&id);
+        if path.exists() {
+            return Ok((id, 0));
+        }
+
+        let object = match self.keys()? {
+            Some(keys) => {
+                let mut nonce = [0u8; NONCE_LEN];
+                rand::rng().fill_bytes(&mut nonce);
+                let mut object = nonce.to_vec();
+                object.extend(keys.session.encrypt_with_aad(chunk, &id, &nonce)?);
+                object
+            },
+            None => chunk.to_vec(),
+        };
+        let subdir = path.parent().expect("objects are in a subdirectory");
+        if !subdir.exists() {
+            fs::create_dir_all(subdir)?;
+            sync_parent_dir(subdir)?;
+        }
+        write_atomic(&path, &object)?;
+        Ok((id, object.len() as u64))
+    }
+
+    // Reads a chunk back, checking it against its id
+    fn get_chunk(&self, id: &ChunkId) -> Result<Vec<u8>, Box<dyn Error>> {
+        let object = fs::read(self.object_path(id)).map_err(|e| format!("chunk {}: {}", hex::encode(id), e))?;
+        let chunk = match self.keys()? {
+            Some(keys) => {
+                if object.len() < NONCE_LEN {
+                    return Err(format!("chunk {}: truncated object", hex::encode(id)).into());
+                }
+                let (nonce, ciphertext) = object.split_at(NONCE_LEN);
+                keys.session
+                    .decrypt_with_aad(ciphertext, id, nonce)
+                    .map_err(|e| format!("chunk {}: {}", hex::encode(id), e))?
+            },
+            None => object,
+        };
+        if self.chunk_id(&chunk)? != *id {
+            return Err(format!("chunk {}: content doesn't match its id", hex::encode(id)).into());
+        }
+        Ok(chunk)
+    }
+
+    // Chunks a file into the store and saves its recipe under a name
+    // (replacing a file of the same name)
+    pub fn put(&self, name: &str, reader: impl Read) -> Result<PutReport, Box<dyn Error>> {
+        let recipe_path = self.recipe_path(name)?;
+        let mut report = PutReport { size: 0, chunks: 0, new_chunks: 0, new_bytes: 0 };
+        let mut recipe = String::new();
+
+        fastcdc::for_each_chunk(reader, self.chunker, |chunk| {
+            let (id, written) = self.put_chunk(chunk)?;
+            recipe.push_str(&format!("{} {}\n", hex::encode(id), chunk.len()));
+            report.size += chunk.len() as u64;
+            report.chunks += 1;
+            if written > 0 {
+                report.new_chunks += 1;
+                report.new_bytes += written;
+            }
+            Ok(())
+        })?;
+
+        // Written last: a recipe only refers to chunks already stored
+        write_atomic(&recipe_path, recipe.as_bytes())?;
+        Ok(report)
+    }
+
+    // Chunk ids and lengths of a stored file
+    fn recipe(&self, name: &str) -> Result<Vec<(ChunkId, u64)>, Box<dyn Error>> {
+        let text = fs::read_to_string(self.recipe_path(name)?).map_err(|e| format!("{}: {}", name, e))?;
+        text.lines()
+            .map(|line| {
+                let malformed = || format!("{}: malformed recipe line {:?}", name, line);
+                let (id, len) = line.split_once(' ').ok_or_else(malformed)?;
+                let id: ChunkId = hex::decode(id).ok().and_then(|id| id.try_into().ok()).ok_or_else(malformed)?;
+                Ok((id, len.parse().map_err(|_| malformed())?))
+            })
+            .collect()
+    }
+
+    // Writes a stored file, chunk by chunk, and returns its size
+    pub fn get(&self, name: &str, mut writer: impl Write) -> Result<u64, Box<dyn Error>> {
+        let mut size = 0;
+        for (id, len) in self.recipe(name)? {
+            let chunk = self.get_chunk(&id)?;
+            if chunk.len() as u64 != len {
+                return Err(format!("chunk {}: {} bytes instead of {}", hex::encode(id), chunk.len(), len).into());
+            }
+            writer.write_all(&chunk)?;
+            size += len;
+        }
+        writer.flush()?;
+        Ok(size)
+    }
+
+    pub fn remove(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        fs::remove_file(self.recipe_path(name)?).map_err(|e| format!("{}: {}", name, e).into())
+    }
+
+    // Stored files, sorted by name
+    pub fn list(&self) -> Result<Vec<StoredFile>, Box<dyn Error>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(self.dir.join("files"))? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if check_name(&name).is_err() {
+                continue;
+            }
+            let recipe = self.recipe(&name)?;
+            files.push(StoredFile { size: recipe.iter().map(|(_, len)| len).sum(), chunks: recipe.len(), name });
+        }
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(files)
+    }
+
+    // Every file of objects/
+    fn objects(&self) -> Result<Vec<ObjectFile>, Box<dyn Error>> {
+        let mut objects = Vec::new();
+        for prefix in fs::read_dir(self.dir.join("objects"))? {
+            let prefix = prefix?;
+            for entry in fs::read_dir(prefix.path())? {
+                let entry = entry?;
+                let hex = format!("{}{}", prefix.file_name().to_string_lossy(), entry.file_name().to_string_lossy());
+                let id = hex::decode(hex).ok().and_then(|id| id.try_into().ok());
+                objects.push(ObjectFile { path: entry.path(), id, size: entry.metadata()?.len() });
+            }
+        }
+        Ok(objects)
+    }
+
+    // Number of objects and their total size on disk
+    pub fn object_usage(&self) -> Result<(usize, u64), Box<dyn Error>> {
+        let objects = self.objects()?;
+        Ok((objects.len(), objects.iter().map(|object| object.size).sum()))
+    }
+
+    // Deletes the objects (and leftover temporary files) no recipe refers to
+    pub fn gc(&self) -> Result<GcReport, Box<dyn Error>> {
+        let mut referenced = HashSet::new();
+        for file in self.list()? {
+            referenced.extend(self.recipe(&file.name)?.into_iter().map(|(id, _)| id));
+        }
+
+        let mut report = GcReport { kept: 0, removed: 0, freed_bytes: 0 };
+        for object in self.objects()? {
+            if object.id.is_some_and(|id| referenced.contains(&id)) {
+                report.kept += 1;
+            } else {
+                fs::remove_file(&object.path)?;
+                report.removed += 1;
+                report.freed_bytes += object.size;
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rust-cryptocraphy-chunk-store-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn get(store: &ChunkStore, name: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        store.get(name, &mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn deduplication_and_gc() {
+        let dir = test_dir("plain");
+        let store = ChunkStore::create(&dir, ChunkerConfig::new(1024).unwrap(), None).unwrap();
+        let data = crate::hash_blake3_xof(b"chunk store test", 64 * 1024, 0);
+
+        let first = store.put("v1", data.as_slice()).unwrap();
+        assert_eq!((first.size, first.new_chunks), (data.len() as u64, first.chunks));
+        assert_eq!(get(&store, "v1"), data);
+
+        // An edited copy only adds the chunks around the edit
+        let mut edited = data.clone();
+        edited.splice(30_000..30_000, b"edit".iter().copied());
+        let second = store.put("v2", edited.as_slice()).unwrap();
+        assert!(second.new_chunks <= 3, "{} new chunks", second.new_chunks);
+        assert_eq!(get(&store, "v2"), edited);
+
+        let names: Vec<String> = store.list().unwrap().into_iter().map(|file| file.name).collect();
+        assert_eq!(names, ["v1", "v2"]);
+
+        // Nothing to collect while both files are stored
+        assert_eq!(store.gc().unwrap().removed, 0);
+        store.remove("v1").unwrap();
+        let report = store.gc().unwrap();
+        assert!(report.removed > 0 && report.removed <= 3, "{} removed", report.removed);
+        assert_eq!(report.kept, second.chunks);
+        assert_eq!(get(&store, "v2"), edited);
+
+        // A corrupted object is detected
+        let (id, _) = store.recipe("v2").unwrap()[0];
+        fs::write(store.object_path(&id), b"corrupted").unwrap();
+        assert!(store.get("v2", Vec::new()).is_err());
+
+        assert!(store.put("../escape", data.as_slice()).is_err());
+        assert!(ChunkStore::create(&dir, ChunkerConfig::default(), None).is_err());
+        assert!(ChunkStore::open(&dir, Some(&[7u8; KEY_LEN])).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn encrypted_store() {
+        let dir = test_dir("encrypted");
+        let key = [7u8; KEY_LEN];
+        let store = ChunkStore::create(&dir, ChunkerConfig::new(1024).unwrap(), Some(&key)).unwrap();
+        let data = crate::hash_blake3_xof(b"encrypted chunk store test", 10_000, 0);
+        store.put("file", data.as_slice()).unwrap();
+        assert_eq!(get(&store, "file"), data);
+
+        // Objects are encrypted and ids are keyed
+        let recipe = store.recipe("file").unwrap();
+        let (id, len) = recipe[0];
+        let object = fs::read(store.object_path(&id)).unwrap();
+        assert_eq!(object.len() as u64, NONCE_LEN as u64 + len + 16);
+        assert_ne!(&object[NONCE_LEN..NONCE_LEN + len as usize], &data[..len as usize]);
+        assert_ne!(id.as_slice(), crate::hash_blake3(&data[..len as usize]));
+
+        // Wrong key, no key: files can be listed but not read
+        assert!(ChunkStore::open(&dir, Some(&[8u8; KEY_LEN])).is_err());
+        let without_key = ChunkStore::open(&dir, None).unwrap();
+        assert!(without_key.get("file", Vec::new()).is_err());
+        assert_eq!(without_key.list().unwrap().len(), 1);
+
+        // An object swapped with another one fails authentication
+        let (other_id, _) = recipe[1];
+        fs::copy(store.object_path(&other_id), store.object_path(&id)).unwrap();
+        assert!(store.get("file", Vec::new()).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_puts_of_the_same_chunks() {
+        let dir = test_dir("concurrent");
+        let key = [9u8; KEY_LEN];
+        ChunkStore::create(&dir, ChunkerConfig::new(1024).unwrap(), Some(&key)).unwrap();
+        let data = crate::hash_blake3_xof(b"concurrent chunk store test", 32 * 1024, 0);
+
+        // Every writer stores the same chunks, each with its own nonces
+        std::thread::scope(|scope| {
+            for i in 0..4 {
+                let (dir, data) = (&dir, &data);
+                scope.spawn(move || {
+                    let store = ChunkStore::open(dir, Some(&key)).unwrap();
+                    store.put(&format!("copy{}", i), data.as_slice()).unwrap();
+                });
+            }
+        });
+
+        let store = ChunkStore::open(&dir, Some(&key)).unwrap();
+        for i in 0..4 {
+            assert_eq!(get(&store, &format!("copy{}", i)), data);
+        }
+        // No temporary file is left behind
+        let leftovers = fs::read_dir(&dir).unwrap().chain(fs::read_dir(dir.join("files")).unwrap()).filter(|entry| {
+            entry.as_ref().unwrap().file_name().to_string_lossy().ends_with(".tmp")
+        });
+        assert_eq!(leftovers.count(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,188 @@
+// ====================================================
+// KEY-COMMITTING AEAD
+// ====================================================
+// AES-GCM and ChaCha20-Poly1305 are not "key-committing": an attacker who
+// chooses the keys can build ONE ciphertext that decrypts successfully under
+// SEVERAL different keys. Systems that try keys until decryption succeeds
+// (password-derived keys, multi-recipient formats, ...) can then be turned
+// into "partitioning oracles" that reveal the key much faster than brute force.
+//
+// This wrapper fixes that by committing to the key:
+// 1. HKDF-SHA256(key, salt = nonce) derives a 32-byte commitment
+//    and a separate encryption key
+// 2. The commitment is prepended to the regular AEAD ciphertext
+// 3. Decryption recomputes the commitment and rejects (in constant time)
+//    any ciphertext whose commitment doesn't match the key
+//
+// Finding a ciphertext valid under two keys now requires an HKDF-SHA256 collision.
+//
+// Output format: commitment (32 bytes) || AEAD ciphertext
+
+use std::error::Error;
+
+use hkdf::Hkdf;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+use crate::Cipher;
+
+// Length of the key commitment prepended to the ciphertext
+const COMMITMENT_LEN: usize = 32;
+
+// Derives the key commitment and the actual AEAD key from the user key.
+// The cipher name is bound into the derivation so each AEAD gets its own keys.
+fn derive_keys(cipher: Cipher, key: &[u8], nonce: &[u8]) -> ([u8; COMMITMENT_LEN], [u8; 32]) {
+    let hkdf = Hkdf::<Sha256>::new(Some(nonce), key);
+
+    let mut commitment = [0u8; COMMITMENT_LEN];
+    let mut enc_key = [0u8; 32];
+    // Both outputs are far below the HKDF-SHA256 limit of 255 * 32 bytes
+    hkdf.expand_multi_info(&[b"key commitment|", cipher.name().as_bytes()], &mut commitment)
+        .expect("valid HKDF output length");
+    hkdf.expand_multi_info(&[b"encryption key|", cipher.name().as_bytes()], &mut enc_key)
+        .expect("valid HKDF output length");
+
+    (commitment, enc_key)
+}
+
+pub fn encrypt_committing(cipher: Cipher, message: &[u8], key: &[u8], nonce: &[u8]) -> Vec<u8> {
+    let (commitment, enc_key) = derive_keys(cipher, key, nonce);
+
+    let mut output = commitment.to_vec();
+    output.extend_from_slice(&cipher.encrypt(message, &enc_key, nonce));
+    output
+}
+
+pub fn decrypt_committing(cipher: Cipher, data: &[u8], key: &[u8], nonce: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    if data.len() < COMMITMENT_LEN {
+        return Err("ciphertext too short to contain a key commitment".into());
+    }
+    let (commitment, ciphertext) = data.split_at(COMMITMENT_LEN);
+
+    // Reject ciphertexts that weren't produced under this key before touching the AEAD
+    let (expected, enc_key) = derive_keys(cipher, key, nonce);
+    if !bool::from(expected.ct_eq(commitment)) {
+        return Err("key commitment mismatch".into());
+    }
+
+    cipher.decrypt(ciphertext, &enc_key, nonce)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::Aes256;
+    use aes::cipher::{BlockEncrypt, KeyInit};
+
+    const NONCE: [u8; 12] = [7u8; 12];
+
+    // Multiplication in GF(2^128) with the bit ordering used by GHASH
+    // (NIST SP 800-38D, algorithm 1)
+    fn gf_mul(x: u128, y: u128) -> u128 {
+        let r = 0xE1u128 << 120;
+        let mut z = 0u128;
+        let mut v = y;
+        for i in 0..128 {
+            if (x >> (127 - i)) & 1 == 1 {
+                z ^= v;
+            }
+            v = if v & 1 == 1 { (v >> 1) ^ r } else { v >> 1 };
+        }
+        z
+    }
+
+    // Inverse in GF(2^128): a^(2^128 - 2)
+    fn gf_inv(a: u128) -> u128 {
+        let mut result = 1u128 << 127; // "1" in GHASH bit ordering
+        for bit in (0..128).rev() {
+            result = gf_mul(result, result);
+            if bit != 0 {
+                result = gf_mul(result, a);
+            }
+        }
+        result
+    }
+
+    fn aes_block(key: &[u8], block: [u8; 16]) -> u128 {
+        let cipher = Aes256::new_from_slice(key).unwrap();
+        let mut block = block.into();
+        cipher.encrypt_block(&mut block);
+        u128::from_be_bytes(block.into())
+    }
+
+    // Builds a one-block AES-GCM ciphertext (block || tag) that is valid under
+    // both keys, by solving the GHASH tag equation for the ciphertext block
+    fn aes_gcm_multi_key_ciphertext(key1: &[u8], key2: &[u8], nonce: &[u8]) -> Vec<u8> {
+        let h1 = aes_block(key1, [0u8; 16]);
+        let h2 = aes_block(key2, [0u8; 16]);
+        let mut j0 = [0u8; 16];
+        j0[..12].copy_from_slice(nonce);
+        j0[15] = 1;
+        let s1 = aes_block(key1, j0);
+        let s2 = aes_block(key2, j0);
+        let lengths = 128u128; // no AAD, 128-bit ciphertext
+
+        // tag_k = s_k ^ c*h_k^2 ^ lengths*h_k, solve tag_1 == tag_2 for c
+        let rhs = s1 ^ s2 ^ gf_mul(lengths, h1 ^ h2);
+        let c = gf_mul(rhs, gf_inv(gf_mul(h1, h1) ^ gf_mul(h2, h2)));
+        let tag = s1 ^ gf_mul(c, gf_mul(h1, h1)) ^ gf_mul(lengths, h1);
+
+        let mut ciphertext = c.to_be_bytes().to_vec();
+        ciphertext.extend_from_slice(&tag.to_be_bytes());
+        ciphertext
+    }
+
+    #[test]
+    fn round_trip_both_ciphers() {
+        let key = [1u8; 32];
+        for cipher in [Cipher::AesGcm, Cipher::ChaCha20Poly1305] {
+            let sealed = encrypt_committing(cipher, b"attack at dawn", &key, &NONCE);
+            let opened = decrypt_committing(cipher, &sealed, &key, &NONCE).unwrap();
+            assert_eq!(opened, b"attack at dawn");
+        }
+    }
+
+    #[test]
+    fn plain_aes_gcm_accepts_multi_key_ciphertext() {
+        let (key1, key2) = ([1u8; 32], [2u8; 32]);
+        let ciphertext = aes_gcm_multi_key_ciphertext(&key1, &key2, &NONCE);
+
+        // The same ciphertext decrypts without error under both keys
+        let plain1 = crate::decrypt_aes_gcm(&ciphertext, &key1, &NONCE).unwrap();
+        let plain2 = crate::decrypt_aes_gcm(&ciphertext, &key2, &NONCE).unwrap();
+        assert_ne!(plain1, plain2);
+    }
+
+    #[test]
+    fn committing_mode_rejects_multi_key_ciphertext() {
+        let (key1, key2) = ([1u8; 32], [2u8; 32]);
+
+        // Best effort for the attacker: a multi-key ciphertext for the derived
+        // encryption keys, behind the commitment to the first key
+        let (commitment1, enc_key1) = derive_keys(Cipher::AesGcm, &key1, &NONCE);
+        let (_, enc_key2) = derive_keys(Cipher::AesGcm, &key2, &NONCE);
+        let inner = aes_gcm_multi_key_ciphertext(&enc_key1, &enc_key2, &NONCE);
+        assert!(crate::decrypt_aes_gcm(&inner, &enc_key2, &NONCE).is_ok());
+
+        let mut data = commitment1.to_vec();
+        data.extend_from_slice(&inner);
+
+        assert!(decrypt_committing(Cipher::AesGcm, &data, &key1, &NONCE).is_ok());
+        assert!(decrypt_committing(Cipher::AesGcm, &data, &key2, &NONCE).is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_key_and_tampering() {
+        let key = [1u8; 32];
+        for cipher in [Cipher::AesGcm, Cipher::ChaCha20Poly1305] {
+            let sealed = encrypt_committing(cipher, b"attack at dawn", &key, &NONCE);
+            assert!(decrypt_committing(cipher, &sealed, &[2u8; 32], &NONCE).is_err());
+
+            let mut tampered = sealed.clone();
+            tampered[0] ^= 1;
+            assert!(decrypt_committing(cipher, &tampered, &key, &NONCE).is_err());
+
+            assert!(decrypt_committing(cipher, &sealed[..16], &key, &NONCE).is_err());
+        }
+    }
+}
@@ -0,0 +1,392 @@
+// ====================================================
+// DIRECTORY HASHING AND MANIFESTS
+// ====================================================
+// A single hash for a whole directory (build outputs, datasets, ...), that
+// only changes when the content changes:
+//
+// 1. The tree is walked with the entries of every directory sorted by name,
+//    so the result doesn't depend on the file system's listing order
+// 2. Each file gets a manifest line "<kind> <mode> <digest> <path>"
+//    (path relative to the root, "/" separated). Directories get a line of
+//    their own ("dir <mode> - <path>"), so adding or removing an empty
+//    directory changes the hash.
+// 3. The directory hash is the hash of the manifest text, which also
+//    records the algorithm and options: the same tree hashed with
+//    different options can't give the same hash
+//
+// Policies:
+// - Modes: the permission bits (e.g. 0755 for an executable) are part of
+//   the hash, or replaced with "-" when they shouldn't matter
+// - Symlinks: skipped, recorded as links (hash of the target path) or
+//   followed (hashed as the file or directory they point to)
+//
+// Two manifests can be compared to list added, removed and changed files.
+
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rayon::ThreadPool;
+
+use crate::checksum::{escape_path, unescape_path};
+use crate::file_hash::{hash_file, thread_pool};
+use crate::hash_registry::HashAlgorithm;
+
+const MANIFEST_VERSION: &str = "# dir-hash v1";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    Skip,
+    Record,
+    Follow,
+}
+
+impl SymlinkPolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "skip" => Some(SymlinkPolicy::Skip),
+            "record" => Some(SymlinkPolicy::Record),
+            "follow" => Some(SymlinkPolicy::Follow),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SymlinkPolicy::Skip => "skip",
+            SymlinkPolicy::Record => "record",
+            SymlinkPolicy::Follow => "follow",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+impl EntryKind {
+    fn name(self) -> &'static str {
+        match self {
+            EntryKind::File => "file",
+            EntryKind::Dir => "dir",
+            EntryKind::Symlink => "link",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "file" => Some(EntryKind::File),
+            "dir" => Some(EntryKind::Dir),
+            "link" => Some(EntryKind::Symlink),
+            _ => None,
+        }
+    }
+}
+
+// One file, directory or symlink of the tree
+#[derive(PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub kind: EntryKind,
+    pub mode: Option<u32>, // Permission bits, None when modes aren't included
+    pub digest: Vec<u8>,   // Hash of the content, of the target path for a symlink, empty for a directory
+    pub path: String,
+}
+
+pub struct Manifest {
+    pub algorithm: &'static HashAlgorithm,
+    pub include_modes: bool,
+    pub symlinks: SymlinkPolicy,
+    pub entries: Vec<ManifestEntry>,
+}
+
+#[cfg(unix)]
+fn permission_bits(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+// Only the read-only flag exists elsewhere
+#[cfg(not(unix))]
+fn permission_bits(metadata: &fs::Metadata) -> u32 {
+    if metadata.permissions().readonly() { 0o444 } else { 0o644 }
+}
+
+impl Manifest {
+    // Walks `dir` and hashes every file with the given algorithm
+    pub fn build(dir: &Path, algorithm: &'static HashAlgorithm, include_modes: bool, symlinks: SymlinkPolicy) -> Result<Self, Box<dyn Error>> {
+        let mut manifest = Manifest { algorithm, include_modes, symlinks, entries: Vec::new() };
+        let mut visited = HashSet::new();
+        let pool = thread_pool(None)?; // Shared by every file of the tree
+        manifest.walk(dir, "", &mut visited, &pool)?;
+        Ok(manifest)
+    }
+
+    fn walk(&mut self, dir: &Path, prefix: &str, visited: &mut HashSet<PathBuf>, pool: &ThreadPool) -> Result<(), Box<dyn Error>> {
+        // Followed symlinks may lead back to a parent directory
+        if !visited.insert(fs::canonicalize(dir)?) {
+            return Err(format!("{}: symlink loop", dir.display()).into());
+        }
+
+        let mut names = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            let name = name.into_string().map_err(|name| format!("{}: file name isn't valid UTF-8: {:?}", dir.display(), name))?;
+            names.push(name);
+        }
+        names.sort();
+
+        for name in names {
+            let path = dir.join(&name);
+            let relative = format!("{}{}", prefix, name);
+            let link_metadata = fs::symlink_metadata(&path)?;
+
+            let metadata = if link_metadata.file_type().is_symlink() {
+                match self.symlinks {
+                    SymlinkPolicy::Skip => continue,
+                    SymlinkPolicy::Record => {
+                        let target = fs::read_link(&path)?;
+                        let target = target.to_str().ok_or_else(|| format!("{}: link target isn't valid UTF-8", path.display()))?;
+                        let mut hasher = self.algorithm.hasher();
+                        hasher.update(target.as_bytes());
+                        self.entries.push(ManifestEntry {
+                            kind: EntryKind::Symlink,
+                            mode: None, // Symlink permissions are meaningless
+                            digest: hasher.finalize(),
+                            path: relative,
+                        });
+                        continue;
+                    },
+                    SymlinkPolicy::Follow => fs::metadata(&path).map_err(|e| format!("{}: {}", path.display(), e))?,
+                }
+            } else {
+                link_metadata
+            };
+
+            if metadata.is_dir() {
+                // Its files are listed after it, with their own entries
+                self.entries.push(ManifestEntry {
+                    kind: EntryKind::Dir,
+                    mode: self.include_modes.then(|| permission_bits(&metadata)),
+                    digest: Vec::new(),
+                    path: relative.clone(),
+                });
+                self.walk(&path, &format!("{}/", relative), visited, pool)?;
+            } else if metadata.is_file() {
+                let digest = hash_file(self.algorithm, &path, pool).map_err(|e| format!("{}: {}", path.display(), e))?;
+                self.entries.push(ManifestEntry {
+                    kind: EntryKind::File,
+                    mode: self.include_modes.then(|| permission_bits(&metadata)),
+                    digest,
+                    path: relative,
+                });
+            }
+            // Sockets, FIFOs and devices have no content to hash
+        }
+
+        visited.remove(&fs::canonicalize(dir)?);
+        Ok(())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "{}\n# algorithm: {}\n# modes: {}\n# symlinks: {}\n",
+            MANIFEST_VERSION,
+            self.algorithm.name,
+            if self.include_modes { "yes" } else { "no" },
+            self.symlinks.name(),
+        );
+        for entry in &self.entries {
+            let mode = entry.mode.map_or("-".to_string(), |mode| format!("{:04o}", mode));
+            let digest = if entry.kind == EntryKind::Dir { "-".to_string() } else { hex::encode(&entry.digest) };
+            text.push_str(&format!("{} {} {} {}\n", entry.kind.name(), mode, digest, escape_path(&entry.path)));
+        }
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut lines = text.lines();
+        match lines.next() {
+            Some(MANIFEST_VERSION) => {},
+            _ => return Err("not a directory manifest".into()),
+        }
+
+        let mut header = |name: &str| -> Result<&str, Box<dyn Error>> {
+            lines.next()
+                .and_then(|line| line.strip_prefix(&format!("# {}: ", name)))
+                .ok_or_else(|| format!("manifest header is missing \"{}\"", name).into())
+        };
+        let algorithm_name = header("algorithm")?;
+        let algorithm = HashAlgorithm::find(algorithm_name).ok_or_else(|| format!("unknown hash algorithm: {}", algorithm_name))?;
+        let include_modes = header("modes")? == "yes";
+        let symlinks = header("symlinks")?;
+        let symlinks = SymlinkPolicy::from_name(symlinks).ok_or_else(|| format!("unknown symlink policy: {}", symlinks))?;
+
+        let mut entries = Vec::new();
+        for line in lines {
+            let mut fields = line.splitn(4, ' ');
+            let (Some(kind), Some(mode), Some(digest), Some(path)) = (fields.next(), fields.next(), fields.next(), fields.next()) else {
+                return Err(format!("malformed manifest line: {:?}", line).into());
+            };
+            let kind = EntryKind::from_name(kind).ok_or_else(|| format!("unknown entry kind: {}", kind))?;
+            entries.push(ManifestEntry {
+                kind,
+                mode: if mode == "-" { None } else { Some(u32::from_str_radix(mode, 8)?) },
+                digest: if kind == EntryKind::Dir && digest == "-" { Vec::new() } else { hex::decode(digest)? },
+                path: unescape_path(path).ok_or_else(|| format!("malformed path: {:?}", path))?,
+            });
+        }
+
+        Ok(Manifest { algorithm, include_modes, symlinks, entries })
+    }
+
+    // Hash of the whole directory: the hash of the manifest text
+    pub fn root_hash(&self) -> Vec<u8> {
+        let mut hasher = self.algorithm.hasher();
+        hasher.update(self.to_text().as_bytes());
+        hasher.finalize()
+    }
+}
+
+pub enum Change<'a> {
+    Added(&'a str),
+    Removed(&'a str),
+    Changed(&'a str),
+}
+
+// Entries added, removed or changed (content, mode or kind) between two manifests, sorted by path
+pub fn diff<'a>(old: &'a Manifest, new: &'a Manifest) -> Result<Vec<Change<'a>>, Box<dyn Error>> {
+    if old.algorithm.name != new.algorithm.name || old.include_modes != new.include_modes || old.symlinks != new.symlinks {
+        return Err("the manifests were built with different algorithms or options".into());
+    }
+
+    let old_entries: BTreeMap<&str, &ManifestEntry> = old.entries.iter().map(|entry| (entry.path.as_str(), entry)).collect();
+    let new_entries: BTreeMap<&str, &ManifestEntry> = new.entries.iter().map(|entry| (entry.path.as_str(), entry)).collect();
+
+    let mut paths: Vec<&str> = old_entries.keys().chain(new_entries.keys()).copied().collect();
+    paths.sort();
+    paths.dedup();
+
+    let changes = paths
+        .into_iter()
+        .filter_map(|path| match (old_entries.get(path), new_entries.get(path)) {
+            (None, Some(_)) => Some(Change::Added(path)),
+            (Some(_), None) => Some(Change::Removed(path)),
+            (Some(old), Some(new)) if old != new => Some(Change::Changed(path)),
+            _ => None,
+        })
+        .collect();
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rust-cryptocraphy-dir-hash-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn build(dir: &Path) -> Manifest {
+        Manifest::build(dir, HashAlgorithm::find("blake3").unwrap(), false, SymlinkPolicy::Record).unwrap()
+    }
+
+    fn changes(old: &Manifest, new: &Manifest) -> Vec<String> {
+        diff(old, new).unwrap().iter().map(|change| match change {
+            Change::Added(path) => format!("+{}", path),
+            Change::Removed(path) => format!("-{}", path),
+            Change::Changed(path) => format!("~{}", path),
+        }).collect()
+    }
+
+    #[test]
+    fn manifest_round_trip_and_diff() {
+        let dir = test_dir("round-trip");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        fs::write(dir.join("sub/a.txt"), "a").unwrap();
+        fs::write(dir.join("new\nline"), "escaped").unwrap();
+
+        let old = build(&dir);
+        let paths: Vec<&str> = old.entries.iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(paths, ["b.txt", "new\nline", "sub", "sub/a.txt"]);
+        assert_eq!(old.entries[2].kind, EntryKind::Dir);
+
+        // Parsing the manifest gives back the same tree and hash
+        let parsed = Manifest::parse(&old.to_text()).unwrap();
+        assert!(parsed.entries == old.entries);
+        assert_eq!(parsed.root_hash(), old.root_hash());
+        assert!(Manifest::parse("# dir-hash v0\n").is_err());
+
+        // Other options give another hash
+        let sha256 = HashAlgorithm::find("sha256").unwrap();
+        assert_ne!(Manifest::build(&dir, sha256, false, SymlinkPolicy::Record).unwrap().root_hash(), old.root_hash());
+
+        fs::write(dir.join("sub/a.txt"), "changed").unwrap();
+        fs::remove_file(dir.join("b.txt")).unwrap();
+        fs::write(dir.join("c.txt"), "c").unwrap();
+        let new = build(&dir);
+        assert_ne!(new.root_hash(), old.root_hash());
+        assert_eq!(changes(&old, &new), ["-b.txt", "+c.txt", "~sub/a.txt"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn renames_and_content_changes() {
+        let dir = test_dir("renames");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}").unwrap();
+        let original = build(&dir);
+
+        // Same content under another name
+        fs::rename(dir.join("src/main.rs"), dir.join("src/lib.rs")).unwrap();
+        let renamed = build(&dir);
+        assert_ne!(renamed.root_hash(), original.root_hash());
+        assert_eq!(changes(&original, &renamed), ["+src/lib.rs", "-src/main.rs"]);
+
+        // Renaming a directory renames everything under it
+        fs::rename(dir.join("src"), dir.join("lib")).unwrap();
+        let moved = build(&dir);
+        assert_eq!(changes(&renamed, &moved), ["+lib", "+lib/lib.rs", "-src", "-src/lib.rs"]);
+
+        // Same size, one byte changed
+        fs::write(dir.join("lib/lib.rs"), "fn main() {]").unwrap();
+        let edited = build(&dir);
+        assert_ne!(edited.root_hash(), moved.root_hash());
+        assert_eq!(changes(&moved, &edited), ["~lib/lib.rs"]);
+
+        // Back to the original content: back to the same hash
+        fs::write(dir.join("lib/lib.rs"), "fn main() {}").unwrap();
+        assert_eq!(build(&dir).root_hash(), moved.root_hash());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn empty_directories_change_the_hash() {
+        let dir = test_dir("empty");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        let without = build(&dir);
+
+        fs::create_dir_all(dir.join("empty/nested")).unwrap();
+        let with = build(&dir);
+        assert_ne!(with.root_hash(), without.root_hash());
+        assert_eq!(changes(&without, &with), ["+empty", "+empty/nested"]);
+
+        // An empty directory and an empty file with the same name differ
+        fs::remove_dir_all(dir.join("empty")).unwrap();
+        fs::write(dir.join("empty"), "").unwrap();
+        let file = build(&dir);
+        assert_ne!(file.root_hash(), with.root_hash());
+        assert_eq!(changes(&with, &file), ["~empty", "-empty/nested"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+// ====================================================
+// ENVELOPE ENCRYPTION WITH KEY ROTATION
+// ====================================================
+// Envelope encryption uses two layers of keys:
+// - A random DEK (Data Encryption Key) encrypts the payload itself
+// - A named KEK (Key Encryption Key, the "master key") encrypts the DEK
+//
+// Only the small wrapped DEK depends on the KEK. When a master key changes,
+// rotation unwraps the DEK with the old KEK and rewraps it with the new one:
+// the (possibly huge) payload ciphertext is never touched.
+//
+// The envelope header (cipher and KEK name) is the associated data of the
+// wrapped DEK: changing either makes unwrapping fail instead of silently
+// using another key or algorithm.
+//
+// Used in cloud KMS services (AWS KMS, Google Cloud KMS), Vault, etc.
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use rand::RngCore;
+
+use crate::session::AeadSession;
+use crate::Cipher;
+
+// Both AEADs use 256-bit keys and 96-bit nonces
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+// Named master keys (KEKs), e.g. "master-2024" and "master-2025"
+#[derive(Default)]
+pub struct KeyRing {
+    keys: HashMap<String, Vec<u8>>,
+}
+
+impl KeyRing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Registers a 256-bit KEK under a name
+    pub fn add(&mut self, name: &str, key: &[u8]) -> Result<(), Box<dyn Error>> {
+        if key.len() != KEY_LEN {
+            return Err(format!("KEK must be {} bytes, got {}", KEY_LEN, key.len()).into());
+        }
+        self.keys.insert(name.to_string(), key.to_vec());
+        Ok(())
+    }
+
+    fn get(&self, name: &str) -> Result<&[u8], Box<dyn Error>> {
+        self.keys
+            .get(name)
+            .map(|key| key.as_slice())
+            .ok_or_else(|| format!("unknown KEK: {}", name).into())
+    }
+}
+
+// An encrypted payload together with everything needed to decrypt it,
+// except the KEK which stays in the key ring
+#[derive(Clone, Debug)]
+pub struct Envelope {
+    pub cipher: Cipher,
+    pub kek_id: String,       // Name of the KEK that wraps the DEK
+    pub wrap_nonce: Vec<u8>,  // Nonce used to wrap the DEK
+    pub wrapped_dek: Vec<u8>, // DEK encrypted under the KEK (with auth tag)
+    pub nonce: Vec<u8>,       // Nonce used to encrypt the payload
+    pub ciphertext: Vec<u8>,  // Payload encrypted under the DEK (with auth tag)
+}
+
+// Fresh random bytes for keys and nonces
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    rand::rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+// Associated data of the wrapped DEK: cipher name and KEK name.
+// Cipher names never contain a NUL byte, so the encoding is unambiguous.
+fn header_aad(cipher: Cipher, kek_id: &str) -> Vec<u8> {
+    let mut aad = cipher.name().as_bytes().to_vec();
+    aad.push(0);
+    aad.extend_from_slice(kek_id.as_bytes());
+    aad
+}
+
+// Wraps a DEK under the named KEK with a fresh nonce
+fn wrap_dek(keyring: &KeyRing, kek_id: &str, cipher: Cipher, dek: &[u8]) -> Result<(Vec<u8>, Vec<u8>), Box<dyn Error>> {
+    let kek = keyring.get(kek_id)?;
+    let wrap_nonce = random_bytes(NONCE_LEN);
+    let wrapped_dek = AeadSession::new(cipher, kek)?.encrypt_with_aad(dek, &header_aad(cipher, kek_id), &wrap_nonce)?;
+    Ok((wrap_nonce, wrapped_dek))
+}
+
+// Recovers the DEK of an envelope using its KEK, checking the header
+fn unwrap_dek(keyring: &KeyRing, envelope: &Envelope) -> Result<Vec<u8>, Box<dyn Error>> {
+    let kek = keyring.get(&envelope.kek_id)?;
+    AeadSession::new(envelope.cipher, kek)?
+        .decrypt_with_aad(&envelope.wrapped_dek, &header_aad(envelope.cipher, &envelope.kek_id), &envelope.wrap_nonce)
+        .map_err(|e| format!("cannot unwrap DEK with KEK {}: {}", envelope.kek_id, e).into())
+}
+
+// Encrypts a payload with a fresh random DEK, then wraps the DEK under the named KEK
+pub fn seal(keyring: &KeyRing, kek_id: &str, cipher: Cipher, plaintext: &[u8]) -> Result<Envelope, Box<dyn Error>> {
+    // A new DEK per payload: compromising one DEK exposes only one payload
+    let dek = random_bytes(KEY_LEN);
+    let nonce = random_bytes(NONCE_LEN);
+    let ciphertext = cipher.encrypt(plaintext, &dek, &nonce);
+
+    let (wrap_nonce, wrapped_dek) = wrap_dek(keyring, kek_id, cipher, &dek)?;
+
+    Ok(Envelope {
+        cipher,
+        kek_id: kek_id.to_string(),
+        wrap_nonce,
+        wrapped_dek,
+        nonce,
+        ciphertext,
+    })
+}
+
+// Unwraps the DEK and decrypts the payload
+pub fn open(keyring: &KeyRing, envelope: &Envelope) -> Result<Vec<u8>, Box<dyn Error>> {
+    let dek = unwrap_dek(keyring, envelope)?;
+    envelope
+        .cipher
+        .decrypt(&envelope.ciphertext, &dek, &envelope.nonce)
+        .map_err(|e| format!("cannot decrypt payload: {}", e).into())
+}
+
+// Rewraps the DEK under a new KEK. The payload nonce and ciphertext are
+// copied unchanged, so rotation cost doesn't depend on the payload size.
+pub fn rotate(keyring: &KeyRing, envelope: &Envelope, new_kek_id: &str) -> Result<Envelope, Box<dyn Error>> {
+    let dek = unwrap_dek(keyring, envelope)?;
+    let (wrap_nonce, wrapped_dek) = wrap_dek(keyring, new_kek_id, envelope.cipher, &dek)?;
+
+    Ok(Envelope {
+        kek_id: new_kek_id.to_string(),
+        wrap_nonce,
+        wrapped_dek,
+        ..envelope.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyring() -> KeyRing {
+        let mut keyring = KeyRing::new();
+        keyring.add("master-v1", &[1u8; KEY_LEN]).unwrap();
+        keyring.add("master-v2", &[2u8; KEY_LEN]).unwrap();
+        keyring
+    }
+
+    #[test]
+    fn seal_open_and_rotate() {
+        let keyring = keyring();
+        for cipher in [Cipher::AesGcm, Cipher::ChaCha20Poly1305] {
+            let sealed = seal(&keyring, "master-v1", cipher, b"payload").unwrap();
+            assert_eq!(open(&keyring, &sealed).unwrap(), b"payload");
+
+            let rotated = rotate(&keyring, &sealed, "master-v2").unwrap();
+            assert_eq!(rotated.kek_id, "master-v2");
+            assert_eq!(rotated.ciphertext, sealed.ciphertext);
+            assert_eq!(open(&keyring, &rotated).unwrap(), b"payload");
+
+            // Once rotated, the old KEK can be retired
+            let mut new_keyring = KeyRing::new();
+            new_keyring.add("master-v2", &[2u8; KEY_LEN]).unwrap();
+            assert_eq!(open(&new_keyring, &rotated).unwrap(), b"payload");
+            assert!(open(&new_keyring, &sealed).is_err());
+        }
+        assert!(KeyRing::new().add("short", &[0u8; 16]).is_err());
+    }
+
+    #[test]
+    fn wrong_kek_is_rejected() {
+        let sealed = seal(&keyring(), "master-v1", Cipher::AesGcm, b"payload").unwrap();
+
+        // Same name, other key
+        let mut other = KeyRing::new();
+        other.add("master-v1", &[3u8; KEY_LEN]).unwrap();
+        assert!(open(&other, &sealed).is_err());
+
+        // Unknown name
+        let unknown = Envelope { kek_id: "master-v3".to_string(), ..sealed.clone() };
+        assert!(open(&keyring(), &unknown).is_err());
+        assert!(seal(&keyring(), "master-v3", Cipher::AesGcm, b"payload").is_err());
+    }
+
+    #[test]
+    fn tampered_header_is_rejected() {
+        // Two names for the same key: only the authenticated header tells them apart
+        let mut keyring = KeyRing::new();
+        keyring.add("alice", &[4u8; KEY_LEN]).unwrap();
+        keyring.add("bob", &[4u8; KEY_LEN]).unwrap();
+        let sealed = seal(&keyring, "alice", Cipher::ChaCha20Poly1305, b"payload").unwrap();
+        assert_eq!(open(&keyring, &sealed).unwrap(), b"payload");
+
+        let renamed = Envelope { kek_id: "bob".to_string(), ..sealed.clone() };
+        assert!(open(&keyring, &renamed).is_err());
+        let other_cipher = Envelope { cipher: Cipher::AesGcm, ..sealed.clone() };
+        assert!(open(&keyring, &other_cipher).is_err());
+
+        let mut flipped = sealed.clone();
+        flipped.wrapped_dek[0] ^= 1;
+        assert!(open(&keyring, &flipped).is_err());
+    }
+}
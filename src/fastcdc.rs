@@ -0,0 +1,239 @@
+// ====================================================
+// CONTENT-DEFINED CHUNKING (FastCDC)
+// ====================================================
+// Deduplication needs the same data to be cut into the same chunks. With
+// fixed-size chunks, inserting one byte at the start of a file shifts every
+// boundary and no chunk matches its old version anymore. Content-defined
+// chunking places boundaries where the content itself says so: after an
+// edit, boundaries resynchronize within a chunk or two.
+//
+// FastCDC (Xia et al., USENIX ATC 2016):
+// 1. A "gear" rolling hash: hash = (hash << 1) + GEAR[byte]. After 64 bytes
+//    the oldest byte has been shifted out, so the top bits of the hash only
+//    depend on the last 64 bytes.
+// 2. A boundary is placed after a byte when the top bits of the hash are
+//    all zero: with n bits checked, one position in 2^n on average.
+// 3. Normalized chunking: before the average size, 2 more bits are checked
+//    (cuts are rarer), after it 2 fewer (cuts are likelier). Chunk sizes
+//    cluster around the average instead of being spread out.
+// 4. Chunks are never smaller than avg/4 (the hash isn't even computed on
+//    these bytes) nor larger than 4 * avg (forced cut).
+//
+// The gear table is generated from a fixed seed: boundaries are stable
+// across runs and machines, but differ from other FastCDC implementations.
+
+use std::error::Error;
+use std::io::{ErrorKind, Read};
+
+// SplitMix64 outputs: 256 fixed pseudo-random 64-bit values
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x6a09e667f3bcc908; // First 64 bits of the SHA-512 IV
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+const GEAR: [u64; 256] = gear_table();
+
+// Mask of the n most significant bits
+fn top_bits(n: u32) -> u64 {
+    !0u64 << (64 - n)
+}
+
+// Chunk sizes: avg/4 minimum, avg average, 4 * avg maximum
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkerConfig {
+    avg_size: usize,
+}
+
+impl ChunkerConfig {
+    pub const MIN_AVG_SIZE: usize = 256;
+    pub const MAX_AVG_SIZE: usize = 1 << 26; // 64 MiB
+
+    // The average size must be a power of two
+    pub fn new(avg_size: usize) -> Result<Self, Box<dyn Error>> {
+        if !avg_size.is_power_of_two() || !(Self::MIN_AVG_SIZE..=Self::MAX_AVG_SIZE).contains(&avg_size) {
+            return Err(format!(
+                "average chunk size must be a power of two between {} and {}, got {}",
+                Self::MIN_AVG_SIZE,
+                Self::MAX_AVG_SIZE,
+                avg_size
+            )
+            .into());
+        }
+        Ok(ChunkerConfig { avg_size })
+    }
+
+    pub fn avg_size(&self) -> usize {
+        self.avg_size
+    }
+
+    pub fn min_size(&self) -> usize {
+        self.avg_size / 4
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.avg_size * 4
+    }
+
+    // Length of the first chunk of data. Unless data is the end of the
+    // input, it must hold at least max_size bytes.
+    pub fn cut(&self, data: &[u8]) -> usize {
+        if data.len() <= self.min_size() {
+            return data.len();
+        }
+        let end = data.len().min(self.max_size());
+        let bits = self.avg_size.trailing_zeros();
+        let (mask_small, mask_large) = (top_bits(bits + 2), top_bits(bits - 2));
+
+        let mut hash = 0u64;
+        for (i, &byte) in data.iter().enumerate().take(end).skip(self.min_size()) {
+            hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+            let mask = if i < self.avg_size { mask_small } else { mask_large };
+            if hash & mask == 0 {
+                return i + 1;
+            }
+        }
+        end
+    }
+}
+
+// 64 KiB on average, as used by backup tools
+impl Default for ChunkerConfig {
+    fn default() -> Self {
+        ChunkerConfig { avg_size: 64 * 1024 }
+    }
+}
+
+// Chunks of a slice, in order
+pub struct Chunks<'a> {
+    config: ChunkerConfig,
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let (chunk, rest) = self.data.split_at(self.config.cut(self.data));
+        self.data = rest;
+        Some(chunk)
+    }
+}
+
+pub fn chunks(data: &[u8], config: ChunkerConfig) -> Chunks<'_> {
+    Chunks { config, data }
+}
+
+// Streaming version for files of any size: at most max_size bytes are
+// buffered. Gives the same chunks as chunks() on the whole input.
+pub fn for_each_chunk<R: Read>(
+    mut reader: R,
+    config: ChunkerConfig,
+    mut f: impl FnMut(&[u8]) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut buffer = vec![0u8; config.max_size()];
+    let mut filled = 0;
+    let mut eof = false;
+
+    loop {
+        while !eof && filled < buffer.len() {
+            match reader.read(&mut buffer[filled..]) {
+                Ok(0) => eof = true,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if filled == 0 {
+            return Ok(());
+        }
+
+        let len = config.cut(&buffer[..filled]);
+        f(&buffer[..len])?;
+        buffer.copy_within(len..filled, 0);
+        filled -= len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deterministic pseudo-random test data
+    fn test_data(len: usize) -> Vec<u8> {
+        crate::hash_blake3_xof(b"fastcdc test data", len, 0)
+    }
+
+    #[test]
+    fn chunk_sizes() {
+        let config = ChunkerConfig::new(4096).unwrap();
+        let data = test_data(1 << 20);
+        let sizes: Vec<usize> = chunks(&data, config).map(|chunk| chunk.len()).collect();
+
+        assert_eq!(sizes.iter().sum::<usize>(), data.len());
+        let (last, rest) = sizes.split_last().unwrap();
+        assert!(rest.iter().all(|&size| (config.min_size()..=config.max_size()).contains(&size)));
+        assert!(*last <= config.max_size());
+
+        // Normalized chunking keeps the average close to the target
+        let average = data.len() / sizes.len();
+        assert!((3000..6000).contains(&average), "average chunk size {}", average);
+
+        // Edge cases: empty input, input shorter than the minimum size
+        assert_eq!(chunks(&[], config).count(), 0);
+        assert_eq!(chunks(&data[..100], config).collect::<Vec<_>>(), [&data[..100]]);
+        assert!(ChunkerConfig::new(5000).is_err());
+        assert!(ChunkerConfig::new(128).is_err());
+    }
+
+    #[test]
+    fn boundaries_resynchronize_after_an_edit() {
+        let config = ChunkerConfig::new(4096).unwrap();
+        let data = test_data(256 * 1024);
+        let mut edited = data.clone();
+        edited.splice(1000..1000, b"inserted bytes".iter().copied());
+        edited.drain(100_000..100_010);
+
+        let original: Vec<&[u8]> = chunks(&data, config).collect();
+        let shared = chunks(&edited, config).filter(|chunk| original.contains(chunk)).count();
+        // Only the chunks around the two edits change
+        assert!(shared + 6 >= original.len(), "{} of {} chunks shared", shared, original.len());
+    }
+
+    // Reader returning a few bytes at a time
+    struct SlowReader<'a>(&'a [u8]);
+
+    impl Read for SlowReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(777);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn streaming_matches_slices() {
+        let config = ChunkerConfig::new(1024).unwrap();
+        let data = test_data(100_000);
+        let mut streamed = Vec::new();
+        for_each_chunk(SlowReader(&data), config, |chunk| {
+            streamed.push(chunk.to_vec());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(streamed, chunks(&data, config).collect::<Vec<_>>());
+    }
+}
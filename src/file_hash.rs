@@ -0,0 +1,104 @@
+// ====================================================
+// FILE HASHING: Multi-threaded Blake3
+// ====================================================
+// hash_blake3 hashes a slice already in memory on a single core.
+// For large files Blake3 can do much better: its tree structure lets
+// independent chunks be hashed in parallel on all cores.
+//
+// How it works:
+// 1. Regular files above PARALLEL_THRESHOLD are memory-mapped (no copy into
+//    a buffer) and hashed with a pool of worker threads (same as the `b3sum`
+//    tool). The pool is created once by the caller and reused for every
+//    file: starting threads costs more than hashing a small file.
+// 2. Small files, pipes and stdin ("-") are read as a stream instead
+// 3. The thread count can be limited, e.g. to leave cores for other work
+//    or to avoid disk thrashing on spinning disks
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use blake3::Hasher as Blake3Hasher;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::hash_registry::{stream_reader, HashAlgorithm, StreamingHasher};
+
+// Below this size, splitting the work between threads doesn't pay off
+const PARALLEL_THRESHOLD: u64 = 128 * 1024;
+
+// Worker threads for update_blake3_from_file.
+// threads: None = one thread per core, Some(n) = at most n threads.
+pub fn thread_pool(threads: Option<usize>) -> io::Result<ThreadPool> {
+    ThreadPoolBuilder::new()
+        .num_threads(threads.unwrap_or(0)) // 0 lets rayon use every core
+        .build()
+        .map_err(io::Error::other)
+}
+
+// Feeds a file (or stdin for "-") into a Blake3 hasher (plain, keyed or key derivation)
+pub fn update_blake3_from_file(hasher: &mut Blake3Hasher, path: &Path, pool: &ThreadPool) -> io::Result<()> {
+    // Pipes and stdin: streaming reads
+    if path == Path::new("-") {
+        hasher.update_reader(io::stdin().lock())?;
+        return Ok(());
+    }
+
+    let file = File::open(path)?;
+    let metadata = file.metadata()?;
+    if !metadata.is_file() || metadata.len() < PARALLEL_THRESHOLD {
+        hasher.update_reader(file)?;
+        return Ok(());
+    }
+
+    // Large files: memory-mapped and hashed in parallel
+    pool.install(|| hasher.update_mmap_rayon(path))?;
+    Ok(())
+}
+
+// Digest of a file (or stdin for "-") with any registered algorithm.
+// Blake3 uses the parallel path above, other algorithms stream the file.
+pub fn hash_file(algorithm: &HashAlgorithm, path: &Path, pool: &ThreadPool) -> io::Result<Vec<u8>> {
+    if algorithm.name == "blake3" {
+        let mut hasher = Blake3Hasher::new();
+        update_blake3_from_file(&mut hasher, path, pool)?;
+        return Ok(hasher.finalize().as_bytes().to_vec());
+    }
+
+    stream_file(algorithm.hasher(), path)
+}
+
+// Streams a file (or stdin for "-") into any hasher, e.g. a tagged hash
+pub fn stream_file(hasher: Box<dyn StreamingHasher>, path: &Path) -> io::Result<Vec<u8>> {
+    if path == Path::new("-") {
+        stream_reader(hasher, io::stdin().lock())
+    } else {
+        stream_reader(hasher, File::open(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parallel_and_streaming_paths_agree() {
+        let dir = std::env::temp_dir().join(format!("rust-cryptocraphy-file-hash-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let blake3 = HashAlgorithm::find("blake3").unwrap();
+
+        // Below the threshold, just above it, and large enough for many threads
+        for len in [0, 1000, PARALLEL_THRESHOLD as usize + 1, 3 * 1024 * 1024 + 17] {
+            let data = crate::hash_blake3_xof(b"file hash test", len, 0);
+            let path = dir.join(format!("{}.bin", len));
+            std::fs::write(&path, &data).unwrap();
+
+            let expected = crate::hash_blake3(&data);
+            for threads in [Some(1), Some(4), None] {
+                assert_eq!(hash_file(blake3, &path, &thread_pool(threads).unwrap()).unwrap(), expected, "{} bytes", len);
+            }
+            // Streaming through the registry hasher
+            assert_eq!(stream_file(blake3.hasher(), &path).unwrap(), expected);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
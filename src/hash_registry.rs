@@ -0,0 +1,191 @@
+// ====================================================
+// HASH ALGORITHM REGISTRY
+// ====================================================
+// Every hash function the tool supports, under a common streaming interface,
+// so commands working on files (checksums, manifests, ...) can use any of
+// them by name.
+//
+// Streaming means the data is fed in pieces (update) and the digest is
+// produced at the end (finalize): a file never needs to fit in memory.
+
+use std::io::{self, Read};
+
+use blake2::{Blake2b512, Blake2s256};
+use blake3::Hasher as Blake3Hasher;
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512, Sha512_224, Sha512_256};
+use sha3::{Keccak256, Sha3_224, Sha3_256, Sha3_384, Sha3_512};
+
+// Incremental hashing shared by all registered algorithms
+pub trait StreamingHasher {
+    fn update(&mut self, data: &[u8]);
+    fn finalize(self: Box<Self>) -> Vec<u8>;
+}
+
+// Adapter for the RustCrypto hashers (SHA-2, SHA-3, RIPEMD-160, Blake2)
+struct DigestHasher<D>(D);
+
+impl<D: Digest> StreamingHasher for DigestHasher<D> {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        self.0.finalize().to_vec()
+    }
+}
+
+impl StreamingHasher for Blake3Hasher {
+    fn update(&mut self, data: &[u8]) {
+        Blake3Hasher::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        Blake3Hasher::finalize(&self).as_bytes().to_vec()
+    }
+}
+
+// SHA-256(SHA-256(data)): the inner hash is streamed, the outer one hashes 32 bytes
+struct DoubleSha256(Sha256);
+
+impl StreamingHasher for DoubleSha256 {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        Sha256::digest(self.0.finalize()).to_vec()
+    }
+}
+
+// RIPEMD160(SHA256(data)), Bitcoin's HASH160
+struct Hash160(Sha256);
+
+impl StreamingHasher for Hash160 {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        Ripemd160::digest(self.0.finalize()).to_vec()
+    }
+}
+
+pub struct HashAlgorithm {
+    pub name: &'static str, // Name used on the command line
+    new_hasher: fn() -> Box<dyn StreamingHasher>,
+}
+
+fn digest_hasher<D: Digest + 'static>() -> Box<dyn StreamingHasher> {
+    Box::new(DigestHasher(D::new()))
+}
+
+pub const ALGORITHMS: &[HashAlgorithm] = &[
+    HashAlgorithm { name: "sha224", new_hasher: digest_hasher::<Sha224> },
+    HashAlgorithm { name: "sha256", new_hasher: digest_hasher::<Sha256> },
+    HashAlgorithm { name: "sha384", new_hasher: digest_hasher::<Sha384> },
+    HashAlgorithm { name: "sha512", new_hasher: digest_hasher::<Sha512> },
+    HashAlgorithm { name: "sha512-224", new_hasher: digest_hasher::<Sha512_224> },
+    HashAlgorithm { name: "sha512-256", new_hasher: digest_hasher::<Sha512_256> },
+    HashAlgorithm { name: "sha3-224", new_hasher: digest_hasher::<Sha3_224> },
+    HashAlgorithm { name: "sha3-256", new_hasher: digest_hasher::<Sha3_256> },
+    HashAlgorithm { name: "sha3-384", new_hasher: digest_hasher::<Sha3_384> },
+    HashAlgorithm { name: "sha3-512", new_hasher: digest_hasher::<Sha3_512> },
+    HashAlgorithm { name: "keccak256", new_hasher: digest_hasher::<Keccak256> },
+    HashAlgorithm { name: "double-sha256", new_hasher: || Box::new(DoubleSha256(Sha256::new())) },
+    HashAlgorithm { name: "ripemd160", new_hasher: digest_hasher::<Ripemd160> },
+    HashAlgorithm { name: "hash160", new_hasher: || Box::new(Hash160(Sha256::new())) },
+    HashAlgorithm { name: "blake2b", new_hasher: digest_hasher::<Blake2b512> },
+    HashAlgorithm { name: "blake2s", new_hasher: digest_hasher::<Blake2s256> },
+    HashAlgorithm { name: "blake3", new_hasher: || Box::new(Blake3Hasher::new()) },
+];
+
+impl HashAlgorithm {
+    pub fn find(name: &str) -> Option<&'static HashAlgorithm> {
+        ALGORITHMS.iter().find(|algorithm| algorithm.name == name)
+    }
+
+    pub fn hasher(&self) -> Box<dyn StreamingHasher> {
+        (self.new_hasher)()
+    }
+
+    // Digest length in bytes (the digest of the empty input is cheap to compute)
+    pub fn digest_size(&self) -> usize {
+        self.hasher().finalize().len()
+    }
+}
+
+// Feeds everything a reader produces into a hasher, 64 KiB at a time
+pub fn stream_reader(mut hasher: Box<dyn StreamingHasher>, mut reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => hasher.update(&buffer[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type OneShot = fn(&[u8]) -> Vec<u8>;
+
+    // The one-shot functions of main.rs, by registry name
+    const ONE_SHOT: &[(&str, OneShot)] = &[
+        ("sha224", crate::hash_sha224),
+        ("sha256", crate::hash_sha256),
+        ("sha384", crate::hash_sha384),
+        ("sha512", crate::hash_sha512),
+        ("sha512-224", crate::hash_sha512_224),
+        ("sha512-256", crate::hash_sha512_256),
+        ("sha3-224", crate::hash_sha3_224),
+        ("sha3-256", crate::hash_sha3_256),
+        ("sha3-384", crate::hash_sha3_384),
+        ("sha3-512", crate::hash_sha3_512),
+        ("keccak256", crate::hash_keccak256),
+        ("double-sha256", crate::hash_double_sha256),
+        ("ripemd160", crate::hash_ripemd160),
+        ("hash160", crate::hash160),
+        ("blake2b", crate::hash_blake2b),
+        ("blake2s", crate::hash_blake2s),
+        ("blake3", crate::hash_blake3),
+    ];
+
+    #[test]
+    fn every_name_resolves() {
+        assert_eq!(ALGORITHMS.len(), ONE_SHOT.len());
+        for algorithm in ALGORITHMS {
+            let found = HashAlgorithm::find(algorithm.name).unwrap();
+            assert_eq!(found.name, algorithm.name);
+            assert!(ONE_SHOT.iter().any(|(name, _)| *name == algorithm.name), "{} has no one-shot function", algorithm.name);
+        }
+        assert!(HashAlgorithm::find("md5").is_none());
+        assert!(HashAlgorithm::find("SHA256").is_none());
+    }
+
+    #[test]
+    fn streamed_matches_one_shot() {
+        // Larger than the 64 KiB read buffer, and not a multiple of any block size
+        let data = crate::hash_blake3_xof(b"hash registry test data", 200_001, 0);
+
+        for (name, one_shot) in ONE_SHOT {
+            let algorithm = HashAlgorithm::find(name).unwrap();
+            let expected = one_shot(&data);
+
+            // Uneven pieces, including an empty one
+            let mut hasher = algorithm.hasher();
+            for piece in [&data[..0], &data[..1], &data[1..1000], &data[1000..]] {
+                hasher.update(piece);
+            }
+            assert_eq!(hasher.finalize(), expected, "{} (update)", name);
+
+            assert_eq!(stream_reader(algorithm.hasher(), &data[..]).unwrap(), expected, "{} (reader)", name);
+            assert_eq!(stream_reader(algorithm.hasher(), &[][..]).unwrap(), one_shot(b""), "{} (empty)", name);
+        }
+    }
+}
@@ -0,0 +1,360 @@
+// ====================================================
+// KNOWN-ANSWER TESTS (self-test)
+// ====================================================
+// Official test vectors run against the functions of this tool, to catch a
+// miscompiled dependency, a wrong parameter or a broken refactoring before
+// any data is processed with them.
+//
+// The vectors live in test_vectors/*.rsp, in the NIST CAVP response file
+// format, and are compiled into the binary:
+//
+//     # comment
+//     [SHA-256]          <- section: the algorithm of the following vectors
+//     Len = 8            <- one "name = value" line per field
+//     Msg = d3
+//     MD = 28969c...
+//                        <- a blank line ends the vector
+//
+// Sources: NIST CAVP and FIPS examples (SHA-2, SHA-3, SHAKE, AES-GCM), the
+// Keccak, RIPEMD-160, BLAKE2 and BLAKE3 reference vectors, RFC 4231 (HMAC),
+// NIST SP 800-38A (AES-CBC/CTR), RFC 8439 (ChaCha20-Poly1305), RFC 8032
+// (Ed25519), BIP-340 (Schnorr signatures) and RFC 6979 (secp256k1 ECDSA).
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use ed25519_dalek::{Signer, SigningKey, Verifier};
+use secp256k1::{ecdsa, Keypair, Message, PublicKey, Secp256k1, SecretKey, XOnlyPublicKey};
+
+use crate::hash_registry::HashAlgorithm;
+use crate::legacy_modes;
+use crate::mac::{self, MacAlgorithm};
+use crate::session::AeadSession;
+use crate::tagged_hash::{tagged_hash, TaggedHasher};
+use crate::Cipher;
+
+const HASH_VECTORS: &str = include_str!("../test_vectors/hashes.rsp");
+const AEAD_VECTORS: &str = include_str!("../test_vectors/aead.rsp");
+const MAC_VECTORS: &str = include_str!("../test_vectors/mac.rsp");
+const SIGNATURE_VECTORS: &str = include_str!("../test_vectors/signatures.rsp");
+
+struct Vector {
+    section: String,
+    line: usize, // First line, to locate failures in the file
+    fields: HashMap<String, String>,
+}
+
+impl Vector {
+    fn text(&self, name: &str) -> Result<&str, Box<dyn Error>> {
+        self.fields.get(name).map(|value| value.as_str()).ok_or_else(|| format!("line {}: missing field {}", self.line, name).into())
+    }
+
+    fn bytes(&self, name: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(hex::decode(self.text(name)?)?)
+    }
+
+    fn optional_bytes(&self, name: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        Ok(self.fields.get(name).map(hex::decode).transpose()?)
+    }
+}
+
+fn parse_vectors(text: &str) -> Result<Vec<Vector>, Box<dyn Error>> {
+    let mut vectors = Vec::new();
+    let mut section = String::new();
+    let mut current: Option<Vector> = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('[') {
+            vectors.extend(current.take());
+            if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                section = name.to_string();
+            }
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let (name, value) = line.split_once('=').ok_or_else(|| format!("line {}: expected \"name = value\"", index + 1))?;
+        let vector = current.get_or_insert_with(|| Vector { section: section.clone(), line: index + 1, fields: HashMap::new() });
+        vector.fields.insert(name.trim().to_string(), value.trim().to_string());
+    }
+    vectors.extend(current);
+    Ok(vectors)
+}
+
+// Results of one vector file
+pub struct SuiteReport {
+    pub name: &'static str,
+    pub passed: usize,
+    pub failures: Vec<String>,
+}
+
+// Runs `check` on every vector; a vector fails with an error or a false result
+fn run_suite(name: &'static str, text: &str, check: fn(&Vector) -> Result<bool, Box<dyn Error>>) -> SuiteReport {
+    let mut report = SuiteReport { name, passed: 0, failures: Vec::new() };
+    let vectors = match parse_vectors(text) {
+        Ok(vectors) => vectors,
+        Err(e) => {
+            report.failures.push(format!("malformed vector file: {}", e));
+            return report;
+        }
+    };
+
+    for vector in &vectors {
+        match check(vector) {
+            Ok(true) => report.passed += 1,
+            Ok(false) => report.failures.push(format!("{} (line {}): wrong result", vector.section, vector.line)),
+            Err(e) => report.failures.push(format!("{} (line {}): {}", vector.section, vector.line, e)),
+        }
+    }
+    report
+}
+
+// One-shot function of main.rs and name in the hash registry
+type HashFunction = fn(&[u8]) -> Vec<u8>;
+
+fn hash_function(section: &str) -> Option<(HashFunction, &'static str)> {
+    let function: (HashFunction, &str) = match section {
+        "SHA-224" => (crate::hash_sha224, "sha224"),
+        "SHA-256" => (crate::hash_sha256, "sha256"),
+        "SHA-384" => (crate::hash_sha384, "sha384"),
+        "SHA-512" => (crate::hash_sha512, "sha512"),
+        "SHA-512/224" => (crate::hash_sha512_224, "sha512-224"),
+        "SHA-512/256" => (crate::hash_sha512_256, "sha512-256"),
+        "SHA3-224" => (crate::hash_sha3_224, "sha3-224"),
+        "SHA3-256" => (crate::hash_sha3_256, "sha3-256"),
+        "SHA3-384" => (crate::hash_sha3_384, "sha3-384"),
+        "SHA3-512" => (crate::hash_sha3_512, "sha3-512"),
+        "Keccak-256" => (crate::hash_keccak256, "keccak256"),
+        "RIPEMD-160" => (crate::hash_ripemd160, "ripemd160"),
+        "HASH160" => (crate::hash160, "hash160"),
+        "SHA-256d" => (crate::hash_double_sha256, "double-sha256"),
+        "BLAKE2b-512" => (crate::hash_blake2b, "blake2b"),
+        "BLAKE2s-256" => (crate::hash_blake2s, "blake2s"),
+        "BLAKE3" => (crate::hash_blake3, "blake3"),
+        _ => return None,
+    };
+    Some(function)
+}
+
+fn check_hash(vector: &Vector) -> Result<bool, Box<dyn Error>> {
+    let message = match vector.fields.get("InputLen") {
+        // BLAKE3 reference input: byte i is i mod 251
+        Some(len) => (0..len.parse::<usize>()?).map(|i| (i % 251) as u8).collect(),
+        None => {
+            let len: usize = vector.text("Len")?.parse()?;
+            let mut message = vector.bytes("Msg")?;
+            message.truncate(len / 8); // Msg is 00 for an empty message
+            message
+        }
+    };
+
+    match vector.section.as_str() {
+        "SHAKE128" | "SHAKE256" => {
+            let expected = vector.bytes("Output")?;
+            let length = vector.text("Outputlen")?.parse::<usize>()? / 8;
+            let output = match vector.section.as_str() {
+                "SHAKE128" => crate::hash_shake128(&message, length),
+                _ => crate::hash_shake256(&message, length),
+            };
+            return Ok(output == expected);
+        },
+        "Tagged-SHA256" => {
+            // Known BIP-340/341 tags start from their precomputed midstate
+            let tag = vector.text("Tag")?;
+            let expected = vector.bytes("MD")?;
+            let mut hasher = TaggedHasher::new(tag);
+            let (first, second) = message.split_at(message.len() / 2);
+            hasher.update(first);
+            hasher.update(second);
+            return Ok(tagged_hash(tag, &message).as_slice() == expected && hasher.finalize().as_slice() == expected);
+        },
+        _ => {},
+    }
+
+    let (function, registry_name) = hash_function(&vector.section).ok_or_else(|| format!("unknown algorithm {}", vector.section))?;
+    let expected = vector.bytes("MD")?;
+
+    if let Some(key) = vector.optional_bytes("Key")? {
+        let digest = match vector.section.as_str() {
+            "BLAKE2b-512" => crate::hash_blake2b_keyed(&message, &key, &[], &[], 64)?,
+            "BLAKE2s-256" => crate::hash_blake2s_keyed(&message, &key, &[], &[], 32)?,
+            _ => return Err(format!("{} has no keyed mode", vector.section).into()),
+        };
+        return Ok(digest == expected);
+    }
+
+    // The one-shot function and the streaming hasher (fed in two parts) must agree
+    let mut hasher = HashAlgorithm::find(registry_name).ok_or("algorithm isn't registered")?.hasher();
+    let (first, second) = message.split_at(message.len() / 2);
+    hasher.update(first);
+    hasher.update(second);
+    Ok(function(&message) == expected && hasher.finalize() == expected)
+}
+
+fn check_aead(vector: &Vector) -> Result<bool, Box<dyn Error>> {
+    let cipher = match vector.section.as_str() {
+        "AES-256-GCM" => Cipher::AesGcm,
+        "ChaCha20-Poly1305" => Cipher::ChaCha20Poly1305,
+        _ => return Err(format!("unknown AEAD {}", vector.section).into()),
+    };
+    let (key, nonce, plaintext) = (vector.bytes("Key")?, vector.bytes("IV")?, vector.bytes("PT")?);
+    let aad = vector.optional_bytes("AAD")?.unwrap_or_default();
+    let mut expected = vector.bytes("CT")?;
+    expected.extend(vector.bytes("Tag")?);
+
+    let session = AeadSession::new(cipher, &key)?;
+    let mut ok = session.encrypt_with_aad(&plaintext, &aad, &nonce)? == expected
+        && session.decrypt_with_aad(&expected, &aad, &nonce)? == plaintext;
+
+    // The one-shot functions take no associated data
+    if aad.is_empty() {
+        ok &= cipher.encrypt(&plaintext, &key, &nonce) == expected && cipher.decrypt(&expected, &key, &nonce)? == plaintext;
+    }
+
+    // A modified tag must be rejected
+    let mut tampered = expected.clone();
+    *tampered.last_mut().expect("the tag isn't empty") ^= 1;
+    ok &= session.decrypt_with_aad(&tampered, &aad, &nonce).is_err();
+    Ok(ok)
+}
+
+// Encryption or decryption function of legacy_modes
+type LegacyFunction = fn(&[u8], &[u8], &[u8], &[u8]) -> Result<Vec<u8>, Box<dyn Error>>;
+
+// MAC key of the encrypt-then-MAC legacy modes, SP 800-38A only covers the encryption
+const LEGACY_MAC_KEY: [u8; 32] = [0x22; 32];
+
+fn check_mac(vector: &Vector) -> Result<bool, Box<dyn Error>> {
+    match vector.section.as_str() {
+        "HMAC-SHA256" | "HMAC-SHA512" => {
+            let algorithm = MacAlgorithm::from_name(&vector.section.to_lowercase()).ok_or("unknown HMAC")?;
+            let (key, message, expected) = (vector.bytes("Key")?, vector.bytes("Msg")?, vector.bytes("Mac")?);
+
+            // Modified and truncated tags must be rejected
+            let mut tampered = expected.clone();
+            tampered[0] ^= 1;
+            Ok(algorithm.compute(&key, &message) == expected
+                && algorithm.verify(&key, &message, &expected)
+                && !algorithm.verify(&key, &message, &tampered)
+                && !algorithm.verify(&key, &message, &expected[..16]))
+        },
+        "AES-256-CBC" | "AES-256-CTR" => {
+            let (encrypt, decrypt): (LegacyFunction, LegacyFunction) = match vector.section.as_str() {
+                "AES-256-CBC" => (legacy_modes::encrypt_aes_cbc_hmac, legacy_modes::decrypt_aes_cbc_hmac),
+                _ => (legacy_modes::encrypt_aes_ctr_hmac, legacy_modes::decrypt_aes_ctr_hmac),
+            };
+            let (key, iv, plaintext, expected) = (vector.bytes("Key")?, vector.bytes("IV")?, vector.bytes("PT")?, vector.bytes("CT")?);
+
+            // Output: ciphertext (CT, then the CBC padding block) || HMAC-SHA256(iv || ciphertext)
+            let output = encrypt(&plaintext, &key, &LEGACY_MAC_KEY, &iv)?;
+            let (ciphertext, tag) = output.split_at(output.len().checked_sub(32).ok_or("output shorter than the tag")?);
+            let mut authenticated = iv.clone();
+            authenticated.extend_from_slice(ciphertext);
+            Ok(ciphertext.starts_with(&expected)
+                && tag == mac::hmac_sha256(&LEGACY_MAC_KEY, &authenticated)
+                && decrypt(&output, &key, &LEGACY_MAC_KEY, &iv)? == plaintext)
+        },
+        _ => Err(format!("unknown MAC or mode {}", vector.section).into()),
+    }
+}
+
+fn check_signature(vector: &Vector) -> Result<bool, Box<dyn Error>> {
+    let message = vector.bytes("Msg")?;
+    let public_key = vector.bytes("PK")?;
+    let signature = vector.bytes("Sig")?;
+
+    match vector.section.as_str() {
+        "Ed25519" => {
+            let secret_key: [u8; 32] = vector.bytes("SK")?.try_into().map_err(|_| "Ed25519 secret keys are 32 bytes")?;
+            let signing_key = SigningKey::from_bytes(&secret_key);
+            let computed = signing_key.sign(&message);
+            Ok(signing_key.verifying_key().to_bytes().as_slice() == public_key
+                && computed.to_bytes().as_slice() == signature
+                && signing_key.verifying_key().verify(&message, &computed).is_ok())
+        },
+        "BIP-340" => {
+            let secp = Secp256k1::new();
+            let expected = vector.text("Result")? == "TRUE";
+
+            // Signing is deterministic given the auxiliary randomness
+            if let Some(secret_key) = vector.optional_bytes("SK")? {
+                let keypair = Keypair::from_seckey_slice(&secp, &secret_key)?;
+                let aux: [u8; 32] = vector.bytes("Aux")?.try_into().map_err(|_| "aux_rand is 32 bytes")?;
+                let computed = secp.sign_schnorr_with_aux_rand(&message, &keypair, &aux);
+                if keypair.x_only_public_key().0.serialize().as_slice() != public_key || computed.as_ref() != signature.as_slice() {
+                    return Ok(false);
+                }
+            }
+
+            // Invalid public keys or signatures count as a failed verification
+            let verified = match (XOnlyPublicKey::from_slice(&public_key), secp256k1::schnorr::Signature::from_slice(&signature)) {
+                (Ok(public_key), Ok(signature)) => secp.verify_schnorr(&signature, &message, &public_key).is_ok(),
+                _ => false,
+            };
+            Ok(verified == expected)
+        },
+        "secp256k1-ECDSA" => {
+            let secp = Secp256k1::new();
+            let secret_key = SecretKey::from_slice(&vector.bytes("SK")?)?;
+            let digest = Message::from_digest_slice(&crate::hash_sha256(&message))?;
+
+            // RFC 6979 nonces make the (low-S) signature deterministic
+            let computed = secp.sign_ecdsa(&digest, &secret_key);
+            if PublicKey::from_secret_key(&secp, &secret_key).serialize().as_slice() != public_key
+                || computed.serialize_compact().as_slice() != signature
+            {
+                return Ok(false);
+            }
+
+            // The expected signature verifies, not against another message
+            let (public_key, signature) = (PublicKey::from_slice(&public_key)?, ecdsa::Signature::from_compact(&signature)?);
+            let other = Message::from_digest_slice(&crate::hash_sha256(&[message.as_slice(), b"!"].concat()))?;
+            Ok(secp.verify_ecdsa(&digest, &signature, &public_key).is_ok() && secp.verify_ecdsa(&other, &signature, &public_key).is_err())
+        },
+        _ => Err(format!("unknown signature scheme {}", vector.section).into()),
+    }
+}
+
+// Every suite, in file order
+pub fn run_all() -> Vec<SuiteReport> {
+    vec![
+        run_suite("Hashes (SHA-2, SHA-3, SHAKE, Keccak, RIPEMD-160, BLAKE2, BLAKE3, tagged)", HASH_VECTORS, check_hash),
+        run_suite("AEAD (AES-256-GCM, ChaCha20-Poly1305)", AEAD_VECTORS, check_aead),
+        run_suite("MAC and legacy modes (HMAC, AES-CBC/CTR)", MAC_VECTORS, check_mac),
+        run_suite("Signatures (Ed25519, BIP-340, secp256k1 ECDSA)", SIGNATURE_VECTORS, check_signature),
+    ]
+}
+
+// Startup check: the first failure, if any
+pub fn power_on_self_test() -> Result<(), Box<dyn Error>> {
+    for report in run_all() {
+        if let Some(failure) = report.failures.first() {
+            return Err(format!("{}: {}", report.name, failure).into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_vector_passes() {
+        for report in run_all() {
+            assert!(report.failures.is_empty(), "{}: {:?}", report.name, report.failures);
+            assert!(report.passed > 0, "{}: no vectors", report.name);
+        }
+    }
+
+    #[test]
+    fn wrong_answers_are_reported() {
+        let text = "[SHA-256]\nLen = 8\nMsg = d3\nMD = 00\n\n[SHA-999]\nLen = 0\nMsg = 00\nMD = 00\n";
+        let report = run_suite("test", text, check_hash);
+        assert_eq!(report.passed, 0);
+        assert_eq!(report.failures, ["SHA-256 (line 2): wrong result", "SHA-999 (line 7): unknown algorithm SHA-999"]);
+    }
+}
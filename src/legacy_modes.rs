@@ -0,0 +1,189 @@
+// ====================================================
+// SYMMETRIC ENCRYPTION: Legacy AES-CBC and AES-CTR
+// ====================================================
+// AES-256-CBC (with PKCS#7 padding) and AES-256-CTR are older block cipher
+// modes still found in many existing systems and file formats.
+//
+// Unlike AES-GCM, these modes provide confidentiality only: an attacker can
+// modify the ciphertext without being detected (and CBC padding errors can
+// leak the plaintext through padding-oracle attacks). They are therefore
+// only exposed here combined with HMAC-SHA256 in encrypt-then-MAC form:
+// 1. Encrypt the message with the encryption key
+// 2. Compute HMAC-SHA256(mac_key, iv || ciphertext) with a separate MAC key
+// 3. On decryption, check the tag (in constant time) BEFORE decrypting
+//
+// Output format: ciphertext || tag (32 bytes)
+
+use std::error::Error;
+
+use aes::Aes256;
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit, StreamCipher};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type Aes256CbcEnc = cbc::Encryptor<Aes256>;
+type Aes256CbcDec = cbc::Decryptor<Aes256>;
+type Aes256Ctr = ctr::Ctr128BE<Aes256>; // 128-bit big-endian counter block
+type HmacSha256 = Hmac<Sha256>;
+
+// Length of the HMAC-SHA256 tag appended to the ciphertext
+const TAG_LEN: usize = 32;
+
+// Computes HMAC-SHA256 over the IV and the ciphertext
+fn compute_tag(mac_key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut mac = HmacSha256::new_from_slice(mac_key)?;
+    mac.update(iv);
+    mac.update(ciphertext);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+// Splits ciphertext || tag and checks the tag in constant time
+fn verify_tag<'a>(data: &'a [u8], mac_key: &[u8], iv: &[u8]) -> Result<&'a [u8], Box<dyn Error>> {
+    if data.len() < TAG_LEN {
+        return Err("ciphertext too short to contain an HMAC tag".into());
+    }
+    let (ciphertext, tag) = data.split_at(data.len() - TAG_LEN);
+
+    let mut mac = HmacSha256::new_from_slice(mac_key)?;
+    mac.update(iv);
+    mac.update(ciphertext);
+    // verify_slice compares in constant time
+    mac.verify_slice(tag).map_err(|_| "HMAC tag mismatch")?;
+
+    Ok(ciphertext)
+}
+
+// AES-256-CBC + HMAC-SHA256: 32-byte encryption key, 32-byte MAC key, 16-byte IV
+pub fn encrypt_aes_cbc_hmac(message: &[u8], enc_key: &[u8], mac_key: &[u8], iv: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    // Encrypt with PKCS#7 padding (output is a multiple of 16 bytes)
+    let cipher = Aes256CbcEnc::new_from_slices(enc_key, iv)?;
+    let mut output = cipher.encrypt_padded_vec_mut::<Pkcs7>(message);
+
+    // Then MAC the IV and the ciphertext
+    let tag = compute_tag(mac_key, iv, &output)?;
+    output.extend_from_slice(&tag);
+    Ok(output)
+}
+
+pub fn decrypt_aes_cbc_hmac(data: &[u8], enc_key: &[u8], mac_key: &[u8], iv: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    // Authenticate first: padding is never checked on forged ciphertexts
+    let ciphertext = verify_tag(data, mac_key, iv)?;
+
+    let cipher = Aes256CbcDec::new_from_slices(enc_key, iv)?;
+    cipher
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+        .map_err(|_| "invalid PKCS#7 padding".into())
+}
+
+// AES-256-CTR + HMAC-SHA256: 32-byte encryption key, 32-byte MAC key, 16-byte initial counter block
+pub fn encrypt_aes_ctr_hmac(message: &[u8], enc_key: &[u8], mac_key: &[u8], iv: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    // CTR is a stream mode: XOR the message with the keystream (no padding)
+    let mut output = message.to_vec();
+    let mut cipher = Aes256Ctr::new_from_slices(enc_key, iv)?;
+    cipher.apply_keystream(&mut output);
+
+    let tag = compute_tag(mac_key, iv, &output)?;
+    output.extend_from_slice(&tag);
+    Ok(output)
+}
+
+pub fn decrypt_aes_ctr_hmac(data: &[u8], enc_key: &[u8], mac_key: &[u8], iv: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let ciphertext = verify_tag(data, mac_key, iv)?;
+
+    // Decryption is the same keystream XOR as encryption
+    let mut output = ciphertext.to_vec();
+    let mut cipher = Aes256Ctr::new_from_slices(enc_key, iv)?;
+    cipher.apply_keystream(&mut output);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cbc::cipher::block_padding::NoPadding;
+
+    const ENC_KEY: [u8; 32] = [0x11; 32];
+    const MAC_KEY: [u8; 32] = [0x22; 32];
+    const IV: [u8; 16] = [0x33; 16];
+
+    // NIST SP 800-38A, F.2.5 (CBC-AES256) and F.5.5 (CTR-AES256)
+    const NIST_KEY: &str = "603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4";
+    const NIST_PLAINTEXT: &str = "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+                                  30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710";
+    const NIST_CBC_IV: &str = "000102030405060708090a0b0c0d0e0f";
+    const NIST_CBC_CIPHERTEXT: &str = "f58c4c04d6e5f1ba779eabfb5f7bfbd69cfc4e967edb808d679f777bc6702c7d\
+                                       39f23369a9d9bacfa530e26304231461b2eb05e2c39be9fcda6c19078c6a9d1b";
+    const NIST_CTR_COUNTER: &str = "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff";
+    const NIST_CTR_CIPHERTEXT: &str = "601ec313775789a5b7a7f504bbf3d228f443e3ca4d62b59aca84e990cacaf5c5\
+                                       2b0930daa23de94ce87017ba2d84988ddfc9c58db67aada613c2dd08457941a6";
+
+    type Encrypt = fn(&[u8], &[u8], &[u8], &[u8]) -> Result<Vec<u8>, Box<dyn Error>>;
+    const MODES: [(Encrypt, Encrypt); 2] = [
+        (encrypt_aes_cbc_hmac, decrypt_aes_cbc_hmac),
+        (encrypt_aes_ctr_hmac, decrypt_aes_ctr_hmac),
+    ];
+
+    #[test]
+    fn nist_vectors() {
+        let key = hex::decode(NIST_KEY).unwrap();
+        let plaintext = hex::decode(NIST_PLAINTEXT).unwrap();
+
+        // CBC: the four blocks, then a full block of PKCS#7 padding, then the tag
+        let iv = hex::decode(NIST_CBC_IV).unwrap();
+        let output = encrypt_aes_cbc_hmac(&plaintext, &key, &MAC_KEY, &iv).unwrap();
+        assert_eq!(output.len(), 64 + 16 + TAG_LEN);
+        assert_eq!(hex::encode(&output[..64]), NIST_CBC_CIPHERTEXT);
+
+        // CTR: no padding
+        let counter = hex::decode(NIST_CTR_COUNTER).unwrap();
+        let output = encrypt_aes_ctr_hmac(&plaintext, &key, &MAC_KEY, &counter).unwrap();
+        assert_eq!(output.len(), 64 + TAG_LEN);
+        assert_eq!(hex::encode(&output[..64]), NIST_CTR_CIPHERTEXT);
+    }
+
+    #[test]
+    fn round_trip() {
+        for (encrypt, decrypt) in MODES {
+            for len in [0, 1, 15, 16, 17, 100] {
+                let message: Vec<u8> = (0..len as u8).collect();
+                let output = encrypt(&message, &ENC_KEY, &MAC_KEY, &IV).unwrap();
+                assert_eq!(decrypt(&output, &ENC_KEY, &MAC_KEY, &IV).unwrap(), message);
+            }
+        }
+    }
+
+    #[test]
+    fn tampering_is_rejected() {
+        for (encrypt, decrypt) in MODES {
+            let output = encrypt(b"attack at dawn, not at dusk", &ENC_KEY, &MAC_KEY, &IV).unwrap();
+
+            for index in [0, output.len() - TAG_LEN - 1, output.len() - 1] {
+                let mut tampered = output.clone();
+                tampered[index] ^= 1;
+                assert!(decrypt(&tampered, &ENC_KEY, &MAC_KEY, &IV).is_err());
+            }
+
+            let mut iv = IV;
+            iv[0] ^= 1;
+            assert!(decrypt(&output, &ENC_KEY, &MAC_KEY, &iv).is_err());
+            assert!(decrypt(&output, &ENC_KEY, &[0x23; 32], &IV).is_err());
+            assert!(decrypt(&output[..TAG_LEN - 1], &ENC_KEY, &MAC_KEY, &IV).is_err());
+        }
+    }
+
+    #[test]
+    fn mac_is_checked_before_padding() {
+        // A block whose last byte (0x00) is invalid PKCS#7 padding
+        let ciphertext = Aes256CbcEnc::new_from_slices(&ENC_KEY, &IV).unwrap().encrypt_padded_vec_mut::<NoPadding>(&[0u8; 16]);
+
+        // With a valid tag, decryption gets as far as the padding check
+        let mut data = ciphertext.clone();
+        data.extend(compute_tag(&MAC_KEY, &IV, &ciphertext).unwrap());
+        assert_eq!(decrypt_aes_cbc_hmac(&data, &ENC_KEY, &MAC_KEY, &IV).unwrap_err().to_string(), "invalid PKCS#7 padding");
+
+        // With a forged tag, it stops at the MAC: no padding oracle
+        *data.last_mut().unwrap() ^= 1;
+        assert_eq!(decrypt_aes_cbc_hmac(&data, &ENC_KEY, &MAC_KEY, &IV).unwrap_err().to_string(), "HMAC tag mismatch");
+    }
+}
@@ -0,0 +1,165 @@
+// ====================================================
+// MESSAGE AUTHENTICATION: HMAC-SHA256 / HMAC-SHA512
+// ====================================================
+// HMAC (Hash-based Message Authentication Code, RFC 2104) turns a hash
+// function into a MAC: only someone knowing the secret key can compute
+// a valid tag for a message.
+//
+// How it works:
+// 1. HMAC(K, m) = H((K ^ opad) || H((K ^ ipad) || m))
+// 2. The nested construction is safe even with SHA-2, whose plain
+//    H(key || message) is vulnerable to length-extension attacks
+// 3. Tags must be compared in constant time, otherwise the comparison
+//    time reveals how many leading bytes of a forged tag are correct
+//
+// Used to authenticate webhooks (GitHub, Stripe), API requests (AWS SigV4), JWT (HS256), etc.
+
+use hmac::{Hmac, Mac};
+use sha2::{Sha256, Sha512};
+
+type HmacSha256 = Hmac<Sha256>;
+type HmacSha512 = Hmac<Sha512>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MacAlgorithm {
+    HmacSha256,
+    HmacSha512,
+}
+
+impl MacAlgorithm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sha256" | "hmac-sha256" => Some(MacAlgorithm::HmacSha256),
+            "sha512" | "hmac-sha512" => Some(MacAlgorithm::HmacSha512),
+            _ => None,
+        }
+    }
+
+    pub fn compute(self, key: &[u8], message: &[u8]) -> Vec<u8> {
+        match self {
+            MacAlgorithm::HmacSha256 => hmac_sha256(key, message),
+            MacAlgorithm::HmacSha512 => hmac_sha512(key, message),
+        }
+    }
+
+    pub fn verify(self, key: &[u8], message: &[u8], tag: &[u8]) -> bool {
+        match self {
+            MacAlgorithm::HmacSha256 => verify_hmac_sha256(key, message, tag),
+            MacAlgorithm::HmacSha512 => verify_hmac_sha512(key, message, tag),
+        }
+    }
+}
+
+// 32-byte tag. HMAC accepts keys of any length (long keys are hashed first).
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+// 64-byte tag
+pub fn hmac_sha512(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha512::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+// Constant-time check of a full-length tag (truncated tags are rejected)
+pub fn verify_hmac_sha256(key: &[u8], message: &[u8], tag: &[u8]) -> bool {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.verify_slice(tag).is_ok()
+}
+
+pub fn verify_hmac_sha512(key: &[u8], message: &[u8], tag: &[u8]) -> bool {
+    let mut mac = HmacSha512::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.verify_slice(tag).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 4231 test cases: (key, data, HMAC-SHA-256, HMAC-SHA-512)
+    fn rfc4231_cases() -> Vec<(Vec<u8>, Vec<u8>, &'static str, &'static str)> {
+        vec![
+            (
+                vec![0x0b; 20],
+                b"Hi There".to_vec(),
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+                "87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cdedaa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854",
+            ),
+            (
+                b"Jefe".to_vec(),
+                b"what do ya want for nothing?".to_vec(),
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+                "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+            ),
+            (
+                vec![0xaa; 20],
+                vec![0xdd; 50],
+                "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+                "fa73b0089d56a284efb0f0756c890be9b1b5dbdd8ee81a3655f83e33b2279d39bf3e848279a722c806b485a47e67c807b946a337bee8942674278859e13292fb",
+            ),
+            (
+                (1..=25).collect(),
+                vec![0xcd; 50],
+                "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+                "b0ba465637458c6990e5a8c5f61d4af7e576d97ff94b872de76f8050361ee3dba91ca5c11aa25eb4d679275cc5788063a5f19741120c4f2de2adebeb10a298dd",
+            ),
+            // Test case 6: key longer than the block size, hashed first
+            (
+                vec![0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+                "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f3526b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598",
+            ),
+            (
+                vec![0xaa; 131],
+                b"This is a test using a larger than block-size key and a larger than block-size data. The key needs to be hashed before being used by the HMAC algorithm.".to_vec(),
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+                "e37b6a775dc87dbaa4dfa9f96e5e3ffddebd71f8867289865df5a32d20cdc944b6022cac3c4982b10d5eeb55c3e4de15134676fb6de0446065c97440fa8c6a58",
+            ),
+        ]
+    }
+
+    #[test]
+    fn rfc4231_vectors() {
+        for (key, data, sha256, sha512) in rfc4231_cases() {
+            assert_eq!(hex::encode(hmac_sha256(&key, &data)), sha256);
+            assert_eq!(hex::encode(hmac_sha512(&key, &data)), sha512);
+            assert!(MacAlgorithm::HmacSha256.verify(&key, &data, &hex::decode(sha256).unwrap()));
+            assert!(MacAlgorithm::HmacSha512.verify(&key, &data, &hex::decode(sha512).unwrap()));
+        }
+    }
+
+    // Test case 5: the RFC only gives the first 128 bits of the output
+    #[test]
+    fn rfc4231_truncated_output() {
+        let (key, data) = ([0x0c; 20], b"Test With Truncation");
+        let sha256 = hmac_sha256(&key, data);
+        let sha512 = hmac_sha512(&key, data);
+        assert_eq!(hex::encode(&sha256[..16]), "a3b6167473100ee06e0c796c2955552b");
+        assert_eq!(hex::encode(&sha512[..16]), "415fad6271580a531d4179bc891d87a6");
+
+        // Truncated tags are not accepted by verify
+        assert!(!verify_hmac_sha256(&key, data, &sha256[..16]));
+        assert!(!verify_hmac_sha512(&key, data, &sha512[..16]));
+        assert!(verify_hmac_sha256(&key, data, &sha256));
+    }
+
+    #[test]
+    fn flipped_bit_is_rejected() {
+        for (key, data, _, _) in rfc4231_cases() {
+            for algorithm in [MacAlgorithm::HmacSha256, MacAlgorithm::HmacSha512] {
+                let tag = algorithm.compute(&key, &data);
+                for bit in [0, 7, tag.len() * 8 - 1] {
+                    let mut forged = tag.clone();
+                    forged[bit / 8] ^= 1 << (bit % 8);
+                    assert!(!algorithm.verify(&key, &data, &forged), "{:?}, bit {}", algorithm, bit);
+                }
+            }
+        }
+    }
+}
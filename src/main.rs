@@ -17,20 +17,57 @@ extern crate secp256k1;
 extern crate blake2;
 extern crate blake3;
 
+mod bao;
+mod bench;
+mod block_header;
+mod checksum;
+mod chunk_store;
+mod committing;
+mod dir_hash;
+mod envelope;
+mod fastcdc;
+mod file_hash;
+mod hash_registry;
+mod kat;
+mod legacy_modes;
+mod mac;
+mod merkle;
+mod nonce_counter;
+mod password;
+mod pow;
+mod session;
+mod tagged_hash;
+mod transparency_log;
+mod wycheproof;
+
 use aes_gcm::{AesGcm, Key, Nonce};
 use aes_gcm::aead::{Aead, KeyInit};
 use hex::FromHex;
 use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::hint::black_box;
+use std::time::Instant;
 use aes_gcm::aes::Aes256;
 use generic_array::typenum::U12;
 use chacha20poly1305::ChaCha20Poly1305;
 use chacha20poly1305::Key as ChaChaKey;
 use chacha20poly1305::Nonce as ChaChaNonce;
-use sha2::{Sha256, Digest};
+use sha2::{Sha224, Sha256, Sha384, Sha512, Sha512_224, Sha512_256, Digest};
 use ed25519_dalek::{Signer, SigningKey, VerifyingKey};
 use secp256k1::{Secp256k1, Message as Secp256k1Message};
-use blake2::{Blake2b512, Blake2s256, Digest as Blake2Digest};
+use blake2::{Blake2b512, Blake2bVar, Blake2bVarCore, Blake2s256, Blake2sVar, Blake2sVarCore};
+use blake2::digest::VariableOutput;
+use blake2::digest::core_api::{Buffer, UpdateCore, VariableOutputCore};
 use blake3::Hasher as Blake3Hasher;
+use ripemd::Ripemd160;
+use sha3::{Keccak256, Sha3_224, Sha3_256, Sha3_384, Sha3_512, Shake128, Shake256};
+use sha3::digest::{ExtendableOutput, XofReader};
+
+// Result of the signature demos: two keys/signature byte strings and the verification outcome
+type SignatureDemoResult = Result<(Vec<u8>, Vec<u8>, bool), Box<dyn std::error::Error>>;
 
 // Converts a hex string to a byte array
 // Example: "0A1B2C" becomes [10, 27, 44]
@@ -61,7 +98,7 @@ fn encrypt_aes_gcm(message: &[u8], key: &[u8], nonce: &[u8]) -> Vec<u8> {
     cipher.encrypt(nonce, message).expect("Encryption failure")
 }
 
-fn decrypt_aes_gcm(ciphertext: &[u8], key: &[u8], nonce: &[u8]) -> Vec<u8> {
+fn decrypt_aes_gcm(ciphertext: &[u8], key: &[u8], nonce: &[u8]) -> Result<Vec<u8>, aes_gcm::aead::Error> {
     // Convert bytes to structures suitable for AES-GCM API
     let key = Key::<Aes256>::from_slice(key);
     let nonce = Nonce::<U12>::from_slice(nonce);
@@ -69,8 +106,8 @@ fn decrypt_aes_gcm(ciphertext: &[u8], key: &[u8], nonce: &[u8]) -> Vec<u8> {
     // Initialize AES-GCM cipher
     let cipher = AesGcm::<Aes256, U12>::new(key);
     
-    // Decrypt message and verify auth tag
-    cipher.decrypt(nonce, ciphertext).expect("Decryption failure")
+    // Decrypt message and verify auth tag (fails if the tag doesn't match)
+    cipher.decrypt(nonce, ciphertext)
 }
 
 // ====================================================
@@ -95,7 +132,7 @@ fn encrypt_chacha20poly1305(message: &[u8], key: &[u8], nonce: &[u8]) -> Vec<u8>
     cipher.encrypt(nonce, message).expect("ChaCha20Poly1305 encryption failure")
 }
 
-fn decrypt_chacha20poly1305(ciphertext: &[u8], key: &[u8], nonce: &[u8]) -> Vec<u8> {
+fn decrypt_chacha20poly1305(ciphertext: &[u8], key: &[u8], nonce: &[u8]) -> Result<Vec<u8>, chacha20poly1305::aead::Error> {
     // Prepare key and nonce
     let key = ChaChaKey::from_slice(key);
     let nonce = ChaChaNonce::from_slice(nonce);
@@ -103,8 +140,52 @@ fn decrypt_chacha20poly1305(ciphertext: &[u8], key: &[u8], nonce: &[u8]) -> Vec<
     // Initialize cipher
     let cipher = ChaCha20Poly1305::new(key);
     
-    // Decrypt and verify authenticity
-    cipher.decrypt(nonce, ciphertext).expect("ChaCha20Poly1305 decryption failure")
+    // Decrypt and verify authenticity (fails if the tag doesn't match)
+    cipher.decrypt(nonce, ciphertext)
+}
+
+// ====================================================
+// AEAD SELECTION
+// ====================================================
+// Lets higher-level schemes (envelope encryption, key commitment, ...)
+// work with either AEAD above. Both use 256-bit keys and 96-bit nonces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cipher {
+    AesGcm,
+    ChaCha20Poly1305,
+}
+
+impl Cipher {
+    fn encrypt(self, message: &[u8], key: &[u8], nonce: &[u8]) -> Vec<u8> {
+        match self {
+            Cipher::AesGcm => encrypt_aes_gcm(message, key, nonce),
+            Cipher::ChaCha20Poly1305 => encrypt_chacha20poly1305(message, key, nonce),
+        }
+    }
+
+    fn decrypt(self, ciphertext: &[u8], key: &[u8], nonce: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let decrypted = match self {
+            Cipher::AesGcm => decrypt_aes_gcm(ciphertext, key, nonce),
+            Cipher::ChaCha20Poly1305 => decrypt_chacha20poly1305(ciphertext, key, nonce),
+        };
+        decrypted.map_err(|_| "authentication tag mismatch".into())
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Cipher::AesGcm => "AES-256-GCM",
+            Cipher::ChaCha20Poly1305 => "ChaCha20-Poly1305",
+        }
+    }
+
+    // Maximum number of messages that may be encrypted under one key with
+    // counter nonces. NIST SP 800-38D limits AES-GCM to 2^32 invocations.
+    fn message_limit(self) -> u64 {
+        match self {
+            Cipher::AesGcm => 1 << 32,
+            Cipher::ChaCha20Poly1305 => u64::MAX, // Bounded by the 64-bit counter
+        }
+    }
 }
 
 // ====================================================
@@ -129,6 +210,91 @@ fn hash_sha256(message: &[u8]) -> Vec<u8> {
     hasher.finalize().to_vec()
 }
 
+// ====================================================
+// HASHING: SHA-2 family (SHA-224, SHA-384, SHA-512, SHA-512/t)
+// ====================================================
+// SHA-256 is one member of the SHA-2 family (FIPS 180-4):
+// - SHA-224: SHA-256 with different initial values, truncated to 224 bits
+// - SHA-512: 64-bit words and 80 rounds, 512-bit digest. Often faster
+//   than SHA-256 on 64-bit CPUs without SHA extensions
+// - SHA-384: SHA-512 with different initial values, truncated to 384 bits
+//   (used in TLS certificates and Subresource Integrity)
+// - SHA-512/224 and SHA-512/256: SHA-512 truncated to 224/256 bits, with
+//   their own initial values. Unlike SHA-256, the truncation makes them
+//   immune to length-extension attacks
+fn hash_sha224(message: &[u8]) -> Vec<u8> {
+    Sha224::digest(message).to_vec()
+}
+
+fn hash_sha384(message: &[u8]) -> Vec<u8> {
+    Sha384::digest(message).to_vec()
+}
+
+fn hash_sha512(message: &[u8]) -> Vec<u8> {
+    Sha512::digest(message).to_vec()
+}
+
+fn hash_sha512_224(message: &[u8]) -> Vec<u8> {
+    Sha512_224::digest(message).to_vec()
+}
+
+fn hash_sha512_256(message: &[u8]) -> Vec<u8> {
+    Sha512_256::digest(message).to_vec()
+}
+
+// ====================================================
+// HASHING: SHA-3, SHAKE and Keccak-256
+// ====================================================
+// SHA-3 (FIPS 202) is built on the Keccak sponge construction, completely
+// different from SHA-2: a large internal state absorbs the message, then
+// is squeezed to produce the output.
+//
+// Variants:
+// - SHA3-224/256/384/512: fixed-size digests, drop-in SHA-2 alternatives
+// - SHAKE128/SHAKE256: XOFs (Extendable Output Functions), any output length
+// - Keccak-256: the original Keccak submission, before NIST changed the
+//   padding. Used by Ethereum (addresses, transaction hashes, Solidity's
+//   keccak256). NOT the same output as SHA3-256!
+fn hash_sha3_224(message: &[u8]) -> Vec<u8> {
+    Sha3_224::digest(message).to_vec()
+}
+
+fn hash_sha3_256(message: &[u8]) -> Vec<u8> {
+    Sha3_256::digest(message).to_vec()
+}
+
+fn hash_sha3_384(message: &[u8]) -> Vec<u8> {
+    Sha3_384::digest(message).to_vec()
+}
+
+fn hash_sha3_512(message: &[u8]) -> Vec<u8> {
+    Sha3_512::digest(message).to_vec()
+}
+
+fn hash_shake128(message: &[u8], length: usize) -> Vec<u8> {
+    let mut hasher = Shake128::default();
+    sha3::digest::Update::update(&mut hasher, message);
+    
+    // Squeeze as many bytes as requested
+    let mut output = vec![0u8; length];
+    XofReader::read(&mut hasher.finalize_xof(), &mut output);
+    output
+}
+
+fn hash_shake256(message: &[u8], length: usize) -> Vec<u8> {
+    let mut hasher = Shake256::default();
+    sha3::digest::Update::update(&mut hasher, message);
+    
+    // Squeeze as many bytes as requested
+    let mut output = vec![0u8; length];
+    XofReader::read(&mut hasher.finalize_xof(), &mut output);
+    output
+}
+
+fn hash_keccak256(message: &[u8]) -> Vec<u8> {
+    Keccak256::digest(message).to_vec()
+}
+
 // ====================================================
 // HASHING: Double SHA-256
 // ====================================================
@@ -147,6 +313,37 @@ fn hash_double_sha256(message: &[u8]) -> Vec<u8> {
     hash_sha256(&hash1)
 }
 
+// ====================================================
+// HASHING: RIPEMD-160 and HASH160 (Bitcoin)
+// ====================================================
+// RIPEMD-160 is a 160-bit hash from the 1990s, designed independently
+// from the NSA-designed SHA family.
+//
+// Bitcoin never uses it alone but as HASH160 = RIPEMD160(SHA256(x)):
+// - P2PKH addresses (1...) and P2WPKH addresses (bc1q...) encode the
+//   HASH160 of a public key
+// - P2SH addresses (3...) encode the HASH160 of a script
+// - The 20-byte result keeps addresses short while SHA-256 underneath
+//   protects against weaknesses of RIPEMD-160
+fn hash_ripemd160(message: &[u8]) -> Vec<u8> {
+    Ripemd160::digest(message).to_vec()
+}
+
+fn hash160(message: &[u8]) -> Vec<u8> {
+    hash_ripemd160(&hash_sha256(message))
+}
+
+// HASH160 of a serialized secp256k1 public key. The compressed (33 bytes)
+// and uncompressed (65 bytes) encodings of the same key give different
+// hashes, hence different addresses: modern wallets use the compressed one.
+fn hash160_public_key(public_key: &secp256k1::PublicKey, compressed: bool) -> Vec<u8> {
+    if compressed {
+        hash160(&public_key.serialize())
+    } else {
+        hash160(&public_key.serialize_uncompressed())
+    }
+}
+
 // ====================================================
 // HASHING: Blake2b
 // ====================================================
@@ -218,6 +415,142 @@ fn hash_blake3(message: &[u8]) -> Vec<u8> {
     output.to_vec()
 }
 
+// ====================================================
+// HASHING: Variable output lengths
+// ====================================================
+// Blake2 and Blake3 are not limited to their default digest sizes:
+// - Blake2b: 1 to 64 bytes, Blake2s: 1 to 32 bytes. The length is a
+//   parameter of the hash, so a 20-byte Blake2b digest is NOT a truncated
+//   64-byte digest (different lengths give unrelated outputs)
+// - Blake3 is an XOF (Extendable Output Function): it can produce any
+//   number of bytes, starting from any position (seek offset) in its
+//   output stream. Shorter outputs ARE prefixes of longer ones.
+fn hash_blake2b_var(message: &[u8], length: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    // Initialize Blake2b hasher with the requested output length
+    let mut hasher = Blake2bVar::new(length)
+        .map_err(|_| format!("Blake2b output length must be 1 to 64 bytes, got {}", length))?;
+    
+    blake2::digest::Update::update(&mut hasher, message);
+    
+    let mut output = vec![0u8; length];
+    hasher.finalize_variable(&mut output).map_err(|_| "Blake2b output buffer size mismatch")?;
+    Ok(output)
+}
+
+fn hash_blake2s_var(message: &[u8], length: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    // Initialize Blake2s hasher with the requested output length
+    let mut hasher = Blake2sVar::new(length)
+        .map_err(|_| format!("Blake2s output length must be 1 to 32 bytes, got {}", length))?;
+    
+    blake2::digest::Update::update(&mut hasher, message);
+    
+    let mut output = vec![0u8; length];
+    hasher.finalize_variable(&mut output).map_err(|_| "Blake2s output buffer size mismatch")?;
+    Ok(output)
+}
+
+fn hash_blake3_xof(message: &[u8], length: usize, offset: u64) -> Vec<u8> {
+    let mut hasher = Blake3Hasher::new();
+    hasher.update(message);
+    blake3_xof_output(&hasher, length, offset)
+}
+
+// Reads `length` bytes of Blake3 output starting at byte `offset`
+fn blake3_xof_output(hasher: &Blake3Hasher, length: usize, offset: u64) -> Vec<u8> {
+    let mut reader = hasher.finalize_xof();
+    reader.set_position(offset);
+    
+    let mut output = vec![0u8; length];
+    reader.fill(&mut output);
+    output
+}
+
+// ====================================================
+// KEYED HASHING AND KEY DERIVATION: Blake2 and Blake3
+// ====================================================
+// Besides plain hashing, Blake2 and Blake3 have built-in modes that replace
+// constructions like HMAC and HKDF:
+// - Keyed Blake2b/Blake2s: the key is processed as a first block, making the
+//   hash a MAC. A salt and a personalization string (e.g. an application
+//   name) make outputs unrelated across uses of the same key
+// - Blake3 keyed_hash: MAC with a 32-byte key, any output length
+// - Blake3 derive_key: derives subkeys from key material and a hardcoded,
+//   globally unique context string ("myapp 2025-01-01 session keys")
+
+// Checks the Blake2 parameter limits (RFC 7693) so the hashers never panic
+fn check_blake2_params(name: &str, key: &[u8], salt: &[u8], personal: &[u8], length: usize, max_len: usize) -> Result<(), Box<dyn Error>> {
+    if key.len() > max_len {
+        return Err(format!("{} key must be at most {} bytes, got {}", name, max_len, key.len()).into());
+    }
+    if salt.len() > max_len / 4 || personal.len() > max_len / 4 {
+        return Err(format!("{} salt and personalization must be at most {} bytes", name, max_len / 4).into());
+    }
+    if length == 0 || length > max_len {
+        return Err(format!("{} output length must be 1 to {} bytes, got {}", name, max_len, length).into());
+    }
+    Ok(())
+}
+
+// Keyed Blake2b: key up to 64 bytes, salt and personalization up to 16 bytes, 1-64 byte output
+fn hash_blake2b_keyed(message: &[u8], key: &[u8], salt: &[u8], personal: &[u8], length: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    check_blake2_params("Blake2b", key, salt, personal, length, 64)?;
+    
+    // Parameter block: output length, key length, salt and personalization
+    let mut core = Blake2bVarCore::new_with_params(salt, personal, key.len(), length);
+    let mut buffer = Buffer::<Blake2bVarCore>::default();
+    
+    // The key is hashed first, padded with zeros to a full 128-byte block
+    if !key.is_empty() {
+        let mut key_block = [0u8; 128];
+        key_block[..key.len()].copy_from_slice(key);
+        buffer.digest_blocks(&key_block, |blocks| core.update_blocks(blocks));
+    }
+    buffer.digest_blocks(message, |blocks| core.update_blocks(blocks));
+    
+    let mut output = Default::default();
+    core.finalize_variable_core(&mut buffer, &mut output);
+    Ok(output[..length].to_vec())
+}
+
+// Keyed Blake2s: key up to 32 bytes, salt and personalization up to 8 bytes, 1-32 byte output
+fn hash_blake2s_keyed(message: &[u8], key: &[u8], salt: &[u8], personal: &[u8], length: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    check_blake2_params("Blake2s", key, salt, personal, length, 32)?;
+    
+    // Parameter block: output length, key length, salt and personalization
+    let mut core = Blake2sVarCore::new_with_params(salt, personal, key.len(), length);
+    let mut buffer = Buffer::<Blake2sVarCore>::default();
+    
+    // The key is hashed first, padded with zeros to a full 64-byte block
+    if !key.is_empty() {
+        let mut key_block = [0u8; 64];
+        key_block[..key.len()].copy_from_slice(key);
+        buffer.digest_blocks(&key_block, |blocks| core.update_blocks(blocks));
+    }
+    buffer.digest_blocks(message, |blocks| core.update_blocks(blocks));
+    
+    let mut output = Default::default();
+    core.finalize_variable_core(&mut buffer, &mut output);
+    Ok(output[..length].to_vec())
+}
+
+// Blake3 keyed_hash: 32-byte key, any output length from any XOF offset
+fn hash_blake3_keyed(message: &[u8], key: &[u8], length: usize, offset: u64) -> Result<Vec<u8>, Box<dyn Error>> {
+    let key: &[u8; 32] = key
+        .try_into()
+        .map_err(|_| format!("Blake3 key must be 32 bytes, got {}", key.len()))?;
+    
+    let mut hasher = Blake3Hasher::new_keyed(key);
+    hasher.update(message);
+    Ok(blake3_xof_output(&hasher, length, offset))
+}
+
+// Blake3 derive_key: derives `length` bytes from key material for a given context
+fn derive_key_blake3(context: &str, key_material: &[u8], length: usize) -> Vec<u8> {
+    let mut hasher = Blake3Hasher::new_derive_key(context);
+    hasher.update(key_material);
+    blake3_xof_output(&hasher, length, 0)
+}
+
 // ====================================================
 // DIGITAL SIGNATURE: Ed25519
 // ====================================================
@@ -229,7 +562,7 @@ fn hash_blake3(message: &[u8]) -> Vec<u8> {
 // - 64-byte signatures
 // - Very fast
 // - Used in Solana, SSH, TLS, and other modern protocols
-fn ed25519_example(message: &[u8]) -> Result<(Vec<u8>, Vec<u8>, bool), Box<dyn std::error::Error>> {
+fn ed25519_example(message: &[u8]) -> SignatureDemoResult {
     // Create deterministic key to reproduce same result each time
     // In real use, this key should be randomly generated and kept secret
     let seed = [1u8; 32]; // Fixed 32-byte seed
@@ -260,7 +593,7 @@ fn ed25519_example(message: &[u8]) -> Result<(Vec<u8>, Vec<u8>, bool), Box<dyn s
 // - Elliptic curve with special properties making it efficient
 // - Used by Bitcoin, Ethereum, and other blockchains
 // - More complex but equally secure as Ed25519
-fn secp256k1_example(message: &[u8]) -> Result<(Vec<u8>, Vec<u8>, bool), Box<dyn std::error::Error>> {
+fn secp256k1_example(message: &[u8]) -> SignatureDemoResult {
     // Initialize Secp256k1 context
     let secp = Secp256k1::new();
     
@@ -292,6 +625,62 @@ fn secp256k1_example(message: &[u8]) -> Result<(Vec<u8>, Vec<u8>, bool), Box<dyn
         verification))
 }
 
+// Options of the Blake hashes:
+// - "--length N" (bytes) and "--offset N" (Blake3 XOF seek position, in bytes)
+// - "--key HEX", "--salt HEX" and "--personal TEXT" for keyed Blake2,
+//   "--key HEX" (32 bytes) for keyed Blake3
+// - "--context TEXT" for Blake3 key derivation
+#[derive(Default)]
+struct HashOptions {
+    length: Option<usize>,
+    offset: u64,
+    key: Option<Vec<u8>>,
+    salt: Vec<u8>,
+    personal: Vec<u8>,
+    context: Option<String>,
+}
+
+impl HashOptions {
+    // Parses the options, returning the remaining arguments
+    fn parse(args: &[String]) -> Result<(HashOptions, Vec<&String>), Box<dyn Error>> {
+        let mut options = HashOptions::default();
+        let mut rest = Vec::new();
+        
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--length" => options.length = Some(option_value(&mut args, arg)?.parse()?),
+                "--offset" => options.offset = option_value(&mut args, arg)?.parse()?,
+                "--key" => options.key = Some(hex::decode(option_value(&mut args, arg)?)?),
+                "--salt" => options.salt = hex::decode(option_value(&mut args, arg)?)?,
+                "--personal" => options.personal = option_value(&mut args, arg)?.as_bytes().to_vec(),
+                "--context" => options.context = Some(option_value(&mut args, arg)?.to_string()),
+                _ => rest.push(arg),
+            }
+        }
+        Ok((options, rest))
+    }
+    
+    // Blake2 parameters that require the keyed/parameterized hasher
+    fn has_blake2_params(&self) -> bool {
+        self.key.is_some() || !self.salt.is_empty() || !self.personal.is_empty()
+    }
+    
+    // Blake3 hasher in the selected mode: plain, keyed_hash or derive_key
+    fn blake3_hasher(&self) -> Result<Blake3Hasher, Box<dyn Error>> {
+        match (&self.key, &self.context) {
+            (Some(_), Some(_)) => Err("--key and --context can't be combined".into()),
+            (Some(key), None) => {
+                let key: &[u8; 32] = key.as_slice().try_into()
+                    .map_err(|_| format!("Blake3 key must be 32 bytes, got {}", key.len()))?;
+                Ok(Blake3Hasher::new_keyed(key))
+            },
+            (None, Some(context)) => Ok(Blake3Hasher::new_derive_key(context)),
+            (None, None) => Ok(Blake3Hasher::new()),
+        }
+    }
+}
+
 // ====================================================
 // Main Program
 // ====================================================
@@ -304,6 +693,21 @@ fn main() {
     // Get command line arguments
     let args: Vec<String> = env::args().collect();
     
+    // Known-answer tests before anything is hashed, encrypted or signed
+    if let Err(e) = kat::power_on_self_test() {
+        eprintln!("Error: self-test failed: {}", e);
+        std::process::exit(1);
+    }
+    
+    // File commands (e.g. "hash FILE...") take their own options
+    if args.len() > 1 && let Some(result) = run_command(&args[1], &args[2..]) {
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    
     // Determine message and method
     let (msg, method) = if args.len() > 2 {
         // If two or more arguments, first is message, second is method
@@ -316,6 +720,19 @@ fn main() {
         (default_msg, "aes")
     };
 
+    // Options after the method, e.g. "blake3 --length 64"
+    let hash_options = match HashOptions::parse(args.get(3..).unwrap_or_default()) {
+        Ok((options, rest)) if rest.is_empty() => options,
+        Ok((_, rest)) => {
+            println!("Unrecognized option: {}", rest[0]);
+            return;
+        },
+        Err(e) => {
+            println!("Invalid option: {}", e);
+            return;
+        }
+    };
+
     println!("== Encryption/Decryption/Hashing ==");
     println!("Method: {}", method);
     println!("Message: {:?}", msg);
@@ -329,13 +746,33 @@ fn main() {
     if method == "all" {
         run_aes_gcm(plain, &key_bytes, &nonce_bytes);
         run_chacha20poly1305(plain, &key_bytes, &nonce_bytes);
+        run_aes_cbc_hmac(plain, &key_bytes);
+        run_aes_ctr_hmac(plain, &key_bytes);
         run_sha256(plain);
+        run_sha224(plain);
+        run_sha384(plain);
+        run_sha512(plain);
+        run_sha512_224(plain);
+        run_sha512_256(plain);
+        run_sha3(plain);
+        run_shake(plain, &hash_options);
+        run_keccak256(plain);
         run_double_sha256(plain);
-        run_blake2b(plain);
-        run_blake2s(plain);
-        run_blake3(plain);
+        run_ripemd160(plain);
+        run_hash160(plain);
+        run_tagged_hash(plain);
+        run_merkle();
+        run_blake2b(plain, &hash_options);
+        run_blake2s(plain, &hash_options);
+        run_blake3(plain, &hash_options);
         run_ed25519(plain);
         run_secp256k1(plain);
+        run_committing(plain, &key_bytes, &nonce_bytes);
+        run_envelope(plain, &key_bytes);
+        run_nonce_counter(plain, &key_bytes);
+        run_session(plain, &key_bytes, &nonce_bytes);
+        run_password(plain);
+        run_chunking(plain);
         return;
     }
 
@@ -343,27 +780,892 @@ fn main() {
     match method {
         "aes" => run_aes_gcm(plain, &key_bytes, &nonce_bytes),
         "chacha" => run_chacha20poly1305(plain, &key_bytes, &nonce_bytes),
+        "aes-cbc" => run_aes_cbc_hmac(plain, &key_bytes),
+        "aes-ctr" => run_aes_ctr_hmac(plain, &key_bytes),
         "sha256" => run_sha256(plain),
+        "sha224" => run_sha224(plain),
+        "sha384" => run_sha384(plain),
+        "sha512" => run_sha512(plain),
+        "sha512-224" => run_sha512_224(plain),
+        "sha512-256" => run_sha512_256(plain),
+        "sha3" => run_sha3(plain),
+        "shake" => run_shake(plain, &hash_options),
+        "keccak256" => run_keccak256(plain),
         "double-sha256" => run_double_sha256(plain),
-        "blake2b" => run_blake2b(plain),
-        "blake2s" => run_blake2s(plain),
-        "blake3" => run_blake3(plain),
+        "ripemd160" => run_ripemd160(plain),
+        "hash160" => run_hash160(plain),
+        "tagged-hash" => run_tagged_hash(plain),
+        "merkle" => run_merkle(),
+        "blake2b" => run_blake2b(plain, &hash_options),
+        "blake2s" => run_blake2s(plain, &hash_options),
+        "blake3" => run_blake3(plain, &hash_options),
         "ed25519" => run_ed25519(plain),
         "secp256k1" => run_secp256k1(plain),
+        "commit" => run_committing(plain, &key_bytes, &nonce_bytes),
+        "envelope" => run_envelope(plain, &key_bytes),
+        "nonce" => run_nonce_counter(plain, &key_bytes),
+        "session" => run_session(plain, &key_bytes, &nonce_bytes),
+        "password" => run_password(plain),
+        "chunking" => run_chunking(plain),
         _ => {
             println!("\nUnrecognized algorithm. Available options:");
             println!("  aes: AES-GCM (default)");
             println!("  chacha: ChaCha20-Poly1305");
+            println!("  aes-cbc: AES-256-CBC + HMAC-SHA256 (legacy)");
+            println!("  aes-ctr: AES-256-CTR + HMAC-SHA256 (legacy)");
             println!("  sha256: SHA-256 (Bitcoin)");
+            println!("  sha224, sha384, sha512, sha512-224, sha512-256: Rest of the SHA-2 family");
+            println!("  sha3: SHA3-224, SHA3-256, SHA3-384 and SHA3-512");
+            println!("  shake: SHAKE128 and SHAKE256 (--length N, 32 and 64 bytes by default)");
+            println!("  keccak256: Keccak-256 (Ethereum)");
             println!("  double-sha256: Double SHA-256 (Bitcoin)");
-            println!("  blake2b: Blake2b (512 bits)");
-            println!("  blake2s: Blake2s (256 bits)");
-            println!("  blake3: Blake3 (256 bits by default)");
+            println!("  ripemd160: RIPEMD-160");
+            println!("  hash160: HASH160 = RIPEMD160(SHA256(x)) (Bitcoin addresses)");
+            println!("  tagged-hash: BIP-340 tagged hashes (BIP0340/challenge, TapLeaf, TapTweak, ...)");
+            println!("  merkle: Bitcoin Merkle root and inclusion proof (block 100000)");
+            println!("  blake2b: Blake2b (512 bits, --length 1-64 bytes, --key HEX --salt HEX --personal TEXT)");
+            println!("  blake2s: Blake2s (256 bits, --length 1-32 bytes, --key HEX --salt HEX --personal TEXT)");
+            println!("  blake3: Blake3 (256 bits by default, --length N --offset N, --key HEX or --context TEXT)");
             println!("  ed25519: Ed25519 (Solana)");
             println!("  secp256k1: Secp256k1 (Bitcoin)");
+            println!("  commit: Key-committing AES-GCM and ChaCha20-Poly1305");
+            println!("  envelope: Envelope encryption with KEK rotation");
+            println!("  nonce: AES-GCM with persistent nonce counters");
+            println!("  session: Reusable cipher sessions vs per-call key setup (timing)");
+            println!("  password: Argon2id, scrypt and bcrypt password hashing, verification and rehash check");
+            println!("  chunking: FastCDC content-defined chunking and deduplication after an edit");
             println!("  all: Run all algorithms");
+            println!("\nFile commands:");
+            println!("  hash [--algo NAME] [--threads N] [-r] FILE...: Digest of files (- for stdin), Blake3 by default");
+            println!("      -r hashes directories recursively, output is compatible with sha256sum/b2sum/b3sum");
+            println!("  hash --check [--algo NAME] SUMS...: verify a checksum file, prints OK or FAILED per file");
+            println!("      BSD lines (\"SHA256 (file) = ...\") are checked with the algorithm they name");
+            println!("      algorithms: {}", hash_registry::ALGORITHMS.iter().map(|a| a.name).collect::<Vec<_>>().join(", "));
+            println!("      Blake3 only: --length N --offset N, --key HEX or --context TEXT");
+            println!("  hash --bip340-tag TAG FILE...: BIP-340 tagged hash SHA256(SHA256(TAG) || SHA256(TAG) || file)");
+            println!("  mac --key HEX [--algo sha256|sha512] [FILE]: HMAC tag of a file or stdin");
+            println!("  mac-verify --key HEX --tag HEX [--algo sha256|sha512] [FILE]: check an HMAC tag");
+            println!("  log append DIR [FILE]: add an entry to an RFC 6962 transparency log");
+            println!("  log head DIR --key-file FILE: Ed25519-signed tree head (FILE or - for stdin holds the");
+            println!("      32-byte secret key, raw or hex)");
+            println!("  log prove DIR INDEX [SIZE]: inclusion proof of an entry");
+            println!("  log consistency DIR OLD [NEW]: consistency proof between two tree sizes");
+            println!("  bao encode FILE OUTBOARD: Bao outboard tree of a file, prints its Blake3 hash");
+            println!("  bao slice FILE OUTBOARD START LEN: verifiable slice of a byte range, to stdout");
+            println!("  bao decode-slice HASH START LEN [SLICE]: verify a slice, verified bytes to stdout");
+            println!("  dir-hash [--algo NAME] [--modes] [--symlinks skip|record|follow] [--manifest FILE] DIR:");
+            println!("      deterministic hash of a directory, optionally with its manifest of per-file digests");
+            println!("  dir-diff OLD_MANIFEST NEW_MANIFEST: files added, removed and changed between two manifests");
+            println!("  bench [--filter TEXT] [--sizes 64,1K,1M] [--time MS] [--json]: throughput of every");
+            println!("      hash, AEAD and signature operation (use cargo run --release)");
+            println!("  mine [--bits HEX] [--threads N] [MESSAGE]: proof-of-work search on a block header");
+            println!("      committing to MESSAGE, nBits 1f00ffff (about 65536 attempts) by default");
+            println!("  block-header HEX|FILE: decode an 80-byte Bitcoin block header (hex, raw or hex file,");
+            println!("      - for stdin), print its hash and check its proof of work");
+            println!("  password hash|verify HASH|needs-rehash HASH [--algo argon2id|scrypt|bcrypt]: password");
+            println!("      hashing (PHC / modular crypt strings), password read from stdin");
+            println!("      costs: --m-cost KIB --t-cost N --p-cost N (Argon2id), --log-n N (scrypt), --cost N (bcrypt)");
+            println!("  selftest: known-answer tests (NIST, RFC 4231, RFC 8439, RFC 8032, RFC 6979, Keccak, RIPEMD-160,");
+            println!("      BLAKE2/BLAKE3, BIP-340 vectors: hashes, HMAC, AES modes, AEAD and signatures),");
+            println!("      also run silently at startup");
+            println!("  wycheproof [DIR]: Wycheproof edge-case suites (*.json, test_vectors/wycheproof by default)");
+            println!("      against the AEAD and signature verification functions, summarized per test group and flag");
+            println!("  store put DIR FILE [--name NAME] [--key HEX] [--avg SIZE]: add a file (- for stdin) to a");
+            println!("      deduplicating store of FastCDC chunks addressed by Blake3, created on first use");
+            println!("      (--key: 32-byte key, chunks encrypted with ChaCha20-Poly1305; --avg: 64K by default)");
+            println!("  store get DIR NAME [--key HEX]: write a stored file to stdout, every chunk verified");
+            println!("  store list DIR | store rm DIR NAME | store gc DIR: list, remove, delete unreferenced chunks");
+        }
+    }
+}
+
+// ====================================================
+// File commands
+// ====================================================
+// Commands like "hash FILE..." work on files instead of a message.
+// Returns None when the first argument isn't a command (it's then the message).
+fn run_command(command: &str, args: &[String]) -> Option<Result<(), Box<dyn Error>>> {
+    match command {
+        "hash" => Some(command_hash(args)),
+        "mac" => Some(command_mac(args, false)),
+        "mac-verify" => Some(command_mac(args, true)),
+        "log" => Some(command_log(args)),
+        "bao" => Some(command_bao(args)),
+        "dir-hash" => Some(command_dir_hash(args)),
+        "dir-diff" => Some(command_dir_diff(args)),
+        "bench" => Some(command_bench(args)),
+        "mine" => Some(command_mine(args)),
+        "block-header" => Some(command_block_header(args)),
+        "password" => Some(command_password(args)),
+        "selftest" => Some(command_selftest(args)),
+        "wycheproof" => Some(command_wycheproof(args)),
+        "store" => Some(command_store(args)),
+        _ => None,
+    }
+}
+
+// Reads the value following an option like "--threads 4"
+fn option_value<'a>(args: &mut impl Iterator<Item = &'a String>, option: &str) -> Result<&'a str, Box<dyn Error>> {
+    args.next()
+        .map(|value| value.as_str())
+        .ok_or_else(|| format!("{} needs a value", option).into())
+}
+
+// hash [--algo NAME] [--threads N] [--length N] [--offset N] [--key HEX | --context TEXT] [-r] FILE...
+// hash --check [--algo NAME] [...] SUMS...
+// hash --bip340-tag TAG FILE...
+// Prints one "<hex digest>  <file>" line per file, like sha256sum/b2sum/b3sum.
+// -r hashes every file under the given directories.
+// --check verifies such a file, printing OK/FAILED, and fails (exit code 1)
+// if any file doesn't match or can't be read. BSD lines ("SHA256 (file) = ...")
+// are checked with the algorithm they name, other lines with --algo (blake3
+// by default).
+// --bip340-tag gives the BIP-340 tagged hash SHA256(SHA256(TAG) || SHA256(TAG) || file).
+fn command_hash(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (hash_options, args) = HashOptions::parse(args)?;
+    let mut algorithm = hash_registry::HashAlgorithm::find("blake3").expect("blake3 is registered");
+    let mut algorithm_given = false;
+    let mut tag = None;
+    let mut threads = None;
+    let mut recursive = false;
+    let mut check = false;
+    let mut files = Vec::new();
+    
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--algo" => {
+                let name = option_value(&mut args, arg)?;
+                algorithm = hash_registry::HashAlgorithm::find(name).ok_or_else(|| format!("unknown hash algorithm: {}", name))?;
+                algorithm_given = true;
+            },
+            "--bip340-tag" => tag = Some(option_value(&mut args, arg)?),
+            // coreutils' --tag means BSD-style output, don't let it pass for a file name
+            "--tag" => return Err("--tag (BSD-style output) isn't supported, use --bip340-tag TAG for a BIP-340 tagged hash".into()),
+            "--threads" => threads = Some(option_value(&mut args, arg)?.parse()?),
+            "-r" | "--recursive" => recursive = true,
+            "-c" | "--check" => check = true,
+            _ => files.push(arg.as_str()),
+        }
+    }
+    
+    // Tagged hashes are always SHA-256
+    if tag.is_some() {
+        if algorithm_given && algorithm.name != "sha256" {
+            return Err(format!("--bip340-tag is a SHA-256 tagged hash, it can't be used with {}", algorithm.name).into());
+        }
+        algorithm = hash_registry::HashAlgorithm::find("sha256").expect("sha256 is registered");
+    }
+    
+    // Output and key options only exist for the Blake3 XOF
+    let is_blake3 = algorithm.name == "blake3";
+    let uses_blake3_options = hash_options.length.is_some() || hash_options.offset > 0
+        || hash_options.key.is_some() || hash_options.context.is_some();
+    if !is_blake3 && (uses_blake3_options || hash_options.has_blake2_params()) {
+        return Err(format!("--length, --offset, --key and --context aren't supported with {}", algorithm.name).into());
+    }
+    if check && recursive {
+        return Err("-r can't be combined with --check".into());
+    }
+    if check && (hash_options.length.is_some() || hash_options.offset > 0) {
+        return Err("--length and --offset can't be combined with --check".into());
+    }
+    
+    // No file: hash stdin
+    if files.is_empty() {
+        files.push("-");
+    }
+    
+    // Blake3 hasher set up once (plain, keyed or key derivation), cloned per file,
+    // and one thread pool for every file
+    let blake3_hasher = hash_options.blake3_hasher()?;
+    let pool = file_hash::thread_pool(threads)?;
+    let hash_file = |algorithm: &hash_registry::HashAlgorithm, file: &Path| -> io::Result<Vec<u8>> {
+        if let Some(tag) = tag {
+            file_hash::stream_file(Box::new(tagged_hash::TaggedHasher::new(tag)), file)
+        } else if algorithm.name == "blake3" {
+            let mut hasher = blake3_hasher.clone();
+            file_hash::update_blake3_from_file(&mut hasher, file, &pool)?;
+            Ok(blake3_xof_output(&hasher, hash_options.length.unwrap_or(32), hash_options.offset))
+        } else {
+            file_hash::hash_file(algorithm, file, &pool)
+        }
+    };
+    
+    if check {
+        let mut summary = checksum::CheckSummary::default();
+        for file in files {
+            let contents = String::from_utf8(read_input(file)?).map_err(|e| format!("{}: {}", file, e))?;
+            let file_summary = checksum::check(&contents, algorithm, hash_file);
+            for warning in file_summary.warnings() {
+                eprintln!("WARNING: {}: {}", file, warning);
+            }
+            if (file_summary.malformed > 0 || file_summary.failed > 0) && !algorithm_given {
+                eprintln!("WARNING: {}: lines without an algorithm name are checked with blake3, use --algo for other algorithms", file);
+            }
+            summary.checked += file_summary.checked;
+            summary.failed += file_summary.failed;
+            summary.unreadable += file_summary.unreadable;
+        }
+        if !summary.is_ok() {
+            return Err("checksum verification failed".into());
+        }
+        return Ok(());
+    }
+    
+    for file in files {
+        let paths = if recursive && file != "-" {
+            checksum::collect_files(Path::new(file)).map_err(|e| format!("{}: {}", file, e))?
+        } else {
+            vec![PathBuf::from(file)]
+        };
+        for path in paths {
+            let hash = hash_file(algorithm, &path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            println!("{}", checksum::format_line(&hash, &path.to_string_lossy()));
+        }
+    }
+    Ok(())
+}
+
+// log append DIR [FILE]
+// log head DIR --key-file FILE
+// log prove DIR INDEX [SIZE]
+// log consistency DIR OLD [NEW]
+// The secret key is read from a file (- for stdin), never from the command
+// line where it would show up in ps and the shell history. SIZE and NEW
+// default to the current size of the log. Proofs are checked against the
+// root hash before being printed.
+fn command_log(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let usage = "usage: log append|head|prove|consistency DIR ...";
+    let (subcommand, dir) = match args {
+        [subcommand, dir, ..] => (subcommand.as_str(), Path::new(dir)),
+        _ => return Err(usage.into()),
+    };
+    let args = &args[2..];
+    // Only appending creates the log, a mistyped DIR is an error otherwise
+    let mut log = if subcommand == "append" {
+        transparency_log::TransparencyLog::open_or_create(dir)?
+    } else {
+        transparency_log::TransparencyLog::open(dir)?
+    };
+    
+    // Optional tree size argument, the whole log by default
+    let size_arg = |arg: Option<&String>| -> Result<u64, Box<dyn Error>> {
+        Ok(match arg {
+            Some(size) => size.parse()?,
+            None => log.size(),
+        })
+    };
+    
+    match subcommand {
+        "append" => {
+            let entry = read_input(args.first().map_or("-", |file| file.as_str()))?;
+            let index = log.append(&entry)?;
+            println!("Appended entry {} (tree size {})", index, log.size());
+        },
+        "head" => {
+            let mut args = args.iter();
+            let key = match (args.next().map(|arg| arg.as_str()), args.next()) {
+                (Some("--key-file"), Some(file)) => read_input(file)?,
+                _ => return Err("usage: log head DIR --key-file FILE".into()),
+            };
+            // 32 raw bytes or 64 hex digits
+            let key = match key.len() {
+                32 => key,
+                _ => hex::decode(String::from_utf8_lossy(&key).trim())?,
+            };
+            let key: [u8; 32] = key.try_into().map_err(|_| "the Ed25519 secret key must be 32 bytes")?;
+            let signing_key = SigningKey::from_bytes(&key);
+            
+            let tree_head = log.sign_tree_head(&signing_key)?;
+            println!("Tree size: {}", tree_head.tree_size);
+            println!("Timestamp: {}", tree_head.timestamp);
+            println!("Root hash: {}", hex::encode(tree_head.root_hash));
+            println!("Signature: {}", hex::encode(tree_head.signature));
+            println!("Public key: {}", hex::encode(signing_key.verifying_key().to_bytes()));
+            println!("Verification: {}", if tree_head.verify(&signing_key.verifying_key()) { "Success" } else { "Failed" });
+        },
+        "prove" => {
+            let index: u64 = args.first().ok_or("usage: log prove DIR INDEX [SIZE]")?.parse()?;
+            let tree_size = size_arg(args.get(1))?;
+            let proof = log.inclusion_proof(index, tree_size)?;
+            let root = log.root_hash(tree_size)?;
+            
+            println!("Entry {} in tree of size {} (root {})", index, tree_size, hex::encode(root));
+            for hash in &proof {
+                println!("  {}", hex::encode(hash));
+            }
+            let leaf = log.leaf(index)?;
+            if !transparency_log::verify_inclusion(&leaf, index, tree_size, &proof, &root) {
+                return Err("inclusion proof doesn't match the root hash".into());
+            }
+        },
+        "consistency" => {
+            let old_size: u64 = args.first().ok_or("usage: log consistency DIR OLD [NEW]")?.parse()?;
+            let new_size = size_arg(args.get(1))?;
+            let proof = log.consistency_proof(old_size, new_size)?;
+            let (old_root, new_root) = (log.root_hash(old_size)?, log.root_hash(new_size)?);
+            
+            println!("Tree of size {} (root {})", old_size, hex::encode(old_root));
+            println!("extended by tree of size {} (root {})", new_size, hex::encode(new_root));
+            for hash in &proof {
+                println!("  {}", hex::encode(hash));
+            }
+            if !transparency_log::verify_consistency(old_size, new_size, &old_root, &new_root, &proof) {
+                return Err("consistency proof doesn't match the root hashes".into());
+            }
+        },
+        _ => return Err(usage.into()),
+    }
+    Ok(())
+}
+
+// bao encode FILE OUTBOARD
+// bao slice FILE OUTBOARD START LEN
+// bao decode-slice HASH START LEN [SLICE]
+// Slices and decoded data are binary and written to stdout. Decoding stops
+// with an error at the first corrupted chunk.
+fn command_bao(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+    match args.as_slice() {
+        ["encode", file, outboard_path] => {
+            let data = fs::File::open(file).map_err(|e| format!("{}: {}", file, e))?;
+            let len = data.metadata()?.len();
+            let (root, outboard) = bao::encode_outboard(io::BufReader::new(data), len)?;
+            fs::write(outboard_path, outboard)?;
+            println!("{}  {}", root.to_hex(), file);
+        },
+        ["slice", file, outboard_path, start, len] => {
+            let data = fs::File::open(file).map_err(|e| format!("{}: {}", file, e))?;
+            let outboard = fs::read(outboard_path).map_err(|e| format!("{}: {}", outboard_path, e))?;
+            let slice = bao::extract_slice(data, &outboard, start.parse()?, len.parse()?)?;
+            io::stdout().lock().write_all(&slice)?;
+        },
+        ["decode-slice", hash, start, len, rest @ ..] if rest.len() <= 1 => {
+            let root = blake3::Hash::from_hex(hash)?;
+            let output = io::stdout().lock();
+            match rest.first() {
+                Some(&file) if file != "-" => {
+                    let slice = fs::File::open(file).map_err(|e| format!("{}: {}", file, e))?;
+                    bao::decode_slice(io::BufReader::new(slice), &root, start.parse()?, len.parse()?, output)?;
+                },
+                _ => {
+                    bao::decode_slice(io::stdin().lock(), &root, start.parse()?, len.parse()?, output)?;
+                },
+            }
+        },
+        _ => return Err("usage: bao encode FILE OUTBOARD | bao slice FILE OUTBOARD START LEN | bao decode-slice HASH START LEN [SLICE]".into()),
+    }
+    Ok(())
+}
+
+// dir-hash [--algo NAME] [--modes] [--symlinks skip|record|follow] [--manifest FILE] DIR
+// Prints "<hex digest>  <dir>". Blake3, no modes and recorded symlinks by default.
+fn command_dir_hash(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut algorithm = hash_registry::HashAlgorithm::find("blake3").expect("blake3 is registered");
+    let mut include_modes = false;
+    let mut symlinks = dir_hash::SymlinkPolicy::Record;
+    let mut manifest_path = None;
+    let mut dir = None;
+    
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--algo" => {
+                let name = option_value(&mut args, arg)?;
+                algorithm = hash_registry::HashAlgorithm::find(name).ok_or_else(|| format!("unknown hash algorithm: {}", name))?;
+            },
+            "--modes" => include_modes = true,
+            "--symlinks" => {
+                let name = option_value(&mut args, arg)?;
+                symlinks = dir_hash::SymlinkPolicy::from_name(name).ok_or_else(|| format!("unknown symlink policy: {}", name))?;
+            },
+            "--manifest" => manifest_path = Some(option_value(&mut args, arg)?),
+            _ => dir = Some(arg.as_str()),
+        }
+    }
+    let dir = dir.ok_or("usage: dir-hash [options] DIR")?;
+    
+    let manifest = dir_hash::Manifest::build(Path::new(dir), algorithm, include_modes, symlinks)?;
+    if let Some(manifest_path) = manifest_path {
+        fs::write(manifest_path, manifest.to_text())?;
+    }
+    println!("{}  {}", hex::encode(manifest.root_hash()), dir);
+    Ok(())
+}
+
+// dir-diff OLD_MANIFEST NEW_MANIFEST
+fn command_dir_diff(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let [old_path, new_path] = args else {
+        return Err("usage: dir-diff OLD_MANIFEST NEW_MANIFEST".into());
+    };
+    let old = dir_hash::Manifest::parse(&fs::read_to_string(old_path).map_err(|e| format!("{}: {}", old_path, e))?)?;
+    let new = dir_hash::Manifest::parse(&fs::read_to_string(new_path).map_err(|e| format!("{}: {}", new_path, e))?)?;
+    
+    let changes = dir_hash::diff(&old, &new)?;
+    for change in &changes {
+        match change {
+            dir_hash::Change::Added(path) => println!("added:   {}", path),
+            dir_hash::Change::Removed(path) => println!("removed: {}", path),
+            dir_hash::Change::Changed(path) => println!("changed: {}", path),
+        }
+    }
+    if changes.is_empty() {
+        println!("No changes ({})", hex::encode(new.root_hash()));
+    }
+    Ok(())
+}
+
+// Message size like "64", "16K" (KiB) or "64M" (MiB)
+fn parse_size(text: &str) -> Result<usize, Box<dyn Error>> {
+    let (number, unit) = match text.strip_suffix(['K', 'k']) {
+        Some(number) => (number, 1024),
+        None => match text.strip_suffix(['M', 'm']) {
+            Some(number) => (number, 1024 * 1024),
+            None => (text, 1),
+        },
+    };
+    let size: usize = number.parse().map_err(|_| format!("invalid size: {}", text))?;
+    Ok(size * unit)
+}
+
+fn format_size(size: usize) -> String {
+    if size >= 1024 * 1024 && size.is_multiple_of(1024 * 1024) {
+        format!("{} MiB", size / (1024 * 1024))
+    } else if size >= 1024 && size.is_multiple_of(1024) {
+        format!("{} KiB", size / 1024)
+    } else {
+        format!("{} B", size)
+    }
+}
+
+// bench [--filter TEXT] [--sizes 64,1K,16K,1M,64M] [--time MS] [--json]
+// Prints a table of throughputs (MB/s) with one column per message size,
+// or one JSON object per measurement. --filter keeps the operations whose
+// group, algorithm or operation name contains TEXT (e.g. "aead", "blake").
+fn command_bench(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut filter = None;
+    let mut sizes = vec![64, 1024, 16 * 1024, 1024 * 1024, 64 * 1024 * 1024];
+    let mut min_time = std::time::Duration::from_millis(200);
+    let mut json = false;
+    
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--filter" => filter = Some(option_value(&mut args, arg)?.to_lowercase()),
+            "--sizes" => sizes = option_value(&mut args, arg)?.split(',').map(parse_size).collect::<Result<_, _>>()?,
+            "--time" => min_time = std::time::Duration::from_millis(option_value(&mut args, arg)?.parse()?),
+            "--json" => json = true,
+            _ => return Err(format!("unknown bench option: {}", arg).into()),
+        }
+    }
+    
+    let benchmarks: Vec<_> = bench::benchmarks()
+        .into_iter()
+        .filter(|benchmark| filter.as_ref().is_none_or(|filter| {
+            [benchmark.group, &benchmark.name, benchmark.operation]
+                .iter()
+                .any(|name| name.to_lowercase().contains(filter.as_str()))
+        }))
+        .collect();
+    if benchmarks.is_empty() {
+        return Err("no benchmark matches the filter".into());
+    }
+    
+    // One buffer for every size, non-zero so nothing can take shortcuts
+    let max_size = sizes.iter().copied().max().unwrap_or(0);
+    let message: Vec<u8> = (0..max_size).map(|i| (i % 251) as u8).collect();
+    
+    if json {
+        println!("[");
+    } else {
+        if cfg!(debug_assertions) {
+            eprintln!("Warning: debug build, use `cargo run --release bench` for meaningful numbers");
+        }
+        print!("{:<10} {:<18} {:<16}", "group", "algorithm", "op");
+        for size in &sizes {
+            print!(" {:>10}", format_size(*size));
+        }
+        println!("\n{:<46}{}", "", format!(" {:>10}", "(MB/s)").repeat(sizes.len()));
+    }
+    
+    let mut first = true;
+    for benchmark in &benchmarks {
+        if !json {
+            print!("{:<10} {:<18} {:<16}", benchmark.group, benchmark.name, benchmark.operation);
+        }
+        for &size in &sizes {
+            let result = benchmark.measure(&message[..size], min_time);
+            if json {
+                // One object per line, printed as soon as it's measured
+                if !first {
+                    println!(",");
+                }
+                print!("  {}", serde_json::to_string(&benchmark.json_result(&result))?);
+                first = false;
+            } else {
+                print!(" {:>10.1}", result.megabytes_per_second());
+                io::stdout().flush()?;
+            }
+        }
+        if !json {
+            println!();
+        }
+    }
+    if json {
+        println!("\n]");
+    }
+    Ok(())
+}
+
+// mine [--bits HEX] [--threads N] [MESSAGE]
+// Builds a block header whose Merkle root is the double SHA-256 of MESSAGE
+// and searches a nonce meeting the nBits target, on every core by default.
+fn command_mine(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut bits: u32 = 0x1f00ffff;
+    let mut threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    let mut message = "Hello, proof of work!";
+    
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bits" => bits = u32::from_str_radix(option_value(&mut args, arg)?.trim_start_matches("0x"), 16)?,
+            "--threads" => threads = option_value(&mut args, arg)?.parse()?,
+            _ => message = arg.as_str(),
         }
     }
+    
+    let header = block_header::BlockHeader {
+        version: 0x2000_0000,
+        prev_block_hash: [0u8; 32],
+        merkle_root: hash_double_sha256(message.as_bytes()).try_into().map_err(|_| "SHA-256 output is 32 bytes")?,
+        time: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs() as u32,
+        bits,
+        nonce: 0,
+    };
+    
+    let target = pow::target_from_bits(bits)?;
+    let expected = pow::expected_attempts(&target);
+    println!("nBits: {:08x}", bits);
+    println!("Target: {}", hex::encode(target));
+    println!("Mining with {} thread(s), {:.0} attempts expected...", threads, expected);
+    
+    let start = Instant::now();
+    let result = pow::mine(&header.serialize(), threads)?;
+    let elapsed = start.elapsed();
+    
+    let mined = block_header::BlockHeader::parse(&result.header)?;
+    println!("Nonce: {} (time {})", mined.nonce, mined.time);
+    println!("Block hash: {}", merkle::hash_to_hex(&mined.block_hash()));
+    println!("Header: {}", hex::encode(result.header));
+    println!("Attempts: {} (expected {:.0}, ratio {:.2})", result.attempts, expected, result.attempts as f64 / expected);
+    println!("Time: {:.3} s", elapsed.as_secs_f64());
+    println!("Hash rate: {:.0} kH/s", result.attempts as f64 / elapsed.as_secs_f64() / 1000.0);
+    Ok(())
+}
+
+// block-header HEX|FILE
+// The header is given as 160 hex characters, or in a file (- for stdin)
+// holding either the raw 80 bytes or their hex encoding.
+// Fails (exit code 1) if the proof of work is invalid or the header isn't 80 bytes.
+fn command_block_header(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let [input] = args else {
+        return Err("usage: block-header HEX|FILE".into());
+    };
+    
+    // Hex on the command line (of any length, checked by parse), unless a file has that name
+    let bytes = match hex::decode(input) {
+        Ok(bytes) if !Path::new(input).exists() => bytes,
+        _ => {
+            let data = read_input(input)?;
+            let hex_bytes = std::str::from_utf8(&data).ok().and_then(|text| hex::decode(text.trim()).ok());
+            match hex_bytes {
+                Some(bytes) if data.len() != pow::HEADER_LEN => bytes,
+                _ => data,
+            }
+        }
+    };
+    let header = block_header::BlockHeader::parse(&bytes)?;
+    
+    println!("Version: {} (0x{:08x})", header.version, header.version);
+    println!("Previous block: {}", merkle::hash_to_hex(&header.prev_block_hash));
+    println!("Merkle root: {}", merkle::hash_to_hex(&header.merkle_root));
+    println!("Time: {}", header.time);
+    println!("nBits: {:08x}", header.bits);
+    println!("Nonce: {}", header.nonce);
+    println!("Block hash: {}", merkle::hash_to_hex(&header.block_hash()));
+    println!("Target: {}", hex::encode(header.target()?));
+    
+    if !header.check_proof_of_work()? {
+        return Err("proof of work is invalid: the block hash is above the target".into());
+    }
+    println!("Proof of work: valid");
+    Ok(())
+}
+
+// password hash [POLICY]
+// password verify HASH [POLICY]
+// password needs-rehash HASH [POLICY]
+// POLICY: [--algo argon2id|scrypt|bcrypt] [--m-cost KIB] [--t-cost N] [--p-cost N] [--log-n N] [--cost N]
+// The password is read from stdin (never from the command line, where other
+// users could see it in the process list); a trailing newline is ignored.
+// verify fails (exit code 1) on a wrong password and reports when the hash
+// should be replaced because the policy changed.
+fn command_password(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let usage = "usage: password hash|verify|needs-rehash [HASH] [--algo NAME] [cost options]";
+    let (subcommand, args) = args.split_first().ok_or(usage)?;
+    let (hash, args) = match subcommand.as_str() {
+        "hash" => (None, args),
+        "verify" | "needs-rehash" => {
+            let (hash, args) = args.split_first().ok_or(usage)?;
+            (Some(hash.as_str()), args)
+        },
+        _ => return Err(usage.into()),
+    };
+    
+    let mut policy = password::PasswordPolicy::default();
+    let (mut m_cost, mut t_cost, mut p_cost) = (policy.argon2.m_cost(), policy.argon2.t_cost(), policy.argon2.p_cost());
+    let mut log_n = policy.scrypt.log_n();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--algo" => {
+                let name = option_value(&mut args, arg)?;
+                policy.algorithm = password::PasswordAlgorithm::from_name(name).ok_or_else(|| format!("unknown password hash algorithm: {}", name))?;
+            },
+            "--m-cost" => m_cost = option_value(&mut args, arg)?.parse()?,
+            "--t-cost" => t_cost = option_value(&mut args, arg)?.parse()?,
+            "--p-cost" => p_cost = option_value(&mut args, arg)?.parse()?,
+            "--log-n" => log_n = option_value(&mut args, arg)?.parse()?,
+            "--cost" => policy.bcrypt_cost = option_value(&mut args, arg)?.parse()?,
+            _ => return Err(format!("unknown option: {}", arg).into()),
+        }
+    }
+    policy.argon2 = argon2::Params::new(m_cost, t_cost, p_cost, None)?;
+    policy.scrypt = scrypt::Params::new(log_n, policy.scrypt.r(), policy.scrypt.p(), scrypt::Params::RECOMMENDED_LEN)?;
+    
+    let read_password = || -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut password = read_input("-")?;
+        if password.ends_with(b"\n") {
+            password.pop();
+            if password.ends_with(b"\r") {
+                password.pop();
+            }
+        }
+        Ok(password)
+    };
+    
+    match (subcommand.as_str(), hash) {
+        ("verify", Some(hash)) => {
+            if !password::verify_password(&read_password()?, hash)? {
+                return Err("wrong password".into());
+            }
+            println!("Password: OK");
+            if password::needs_rehash(hash, &policy)? {
+                println!("Needs rehash: yes (the hash doesn't match the current algorithm or costs)");
+            }
+        },
+        ("needs-rehash", Some(hash)) => {
+            println!("{}", if password::needs_rehash(hash, &policy)? { "yes" } else { "no" });
+        },
+        _ => println!("{}", password::hash_password(&read_password()?, &policy)?),
+    }
+    Ok(())
+}
+
+// selftest
+// Runs the known-answer tests of test_vectors/ and prints a summary per
+// suite. Fails (exit code 1) if any vector gives a wrong answer.
+fn command_selftest(args: &[String]) -> Result<(), Box<dyn Error>> {
+    if !args.is_empty() {
+        return Err("usage: selftest".into());
+    }
+    
+    let mut failed = 0;
+    for report in kat::run_all() {
+        let total = report.passed + report.failures.len();
+        println!("{}: {}/{} passed", report.name, report.passed, total);
+        for failure in &report.failures {
+            println!("  FAILED {}", failure);
+        }
+        failed += report.failures.len();
+    }
+    
+    if failed > 0 {
+        return Err(format!("{} known-answer test(s) failed", failed).into());
+    }
+    println!("All known-answer tests passed");
+    Ok(())
+}
+
+// wycheproof [DIR]
+// Runs the Wycheproof JSON suites of DIR (test_vectors/wycheproof by
+// default) and prints the results of each test group and flag. Fails (exit code 1)
+// if a valid input is rejected or an invalid one accepted.
+fn command_wycheproof(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let dir = match args {
+        [] => "test_vectors/wycheproof",
+        [dir] => dir.as_str(),
+        _ => return Err("usage: wycheproof [DIR]".into()),
+    };
+    
+    let mut failed = 0;
+    for report in wycheproof::run_dir(Path::new(dir))? {
+        println!("{} ({}):", report.file, report.algorithm);
+        for group in &report.groups {
+            let run = group.passed + group.failures.len();
+            match (run, group.skipped) {
+                (0, skipped) => println!("  {}: {} skipped (unsupported sizes)", group.description, skipped),
+                (run, 0) => println!("  {}: {}/{} passed", group.description, group.passed, run),
+                (run, skipped) => println!("  {}: {}/{} passed, {} skipped", group.description, group.passed, run, skipped),
+            }
+            for failure in &group.failures {
+                println!("    FAILED {}", failure);
+            }
+            failed += group.failures.len();
+        }
+        println!("  By flag:");
+        for (flag, counts) in report.flags() {
+            println!("    {:<29} {} passed, {} failed, {} skipped", flag, counts.passed, counts.failed, counts.skipped);
+        }
+    }
+    
+    if failed > 0 {
+        return Err(format!("{} Wycheproof test(s) failed", failed).into());
+    }
+    println!("All Wycheproof tests passed");
+    Ok(())
+}
+
+// store put DIR FILE [--name NAME] [--key HEX] [--avg SIZE]
+// store get DIR NAME [--key HEX]
+// store list DIR
+// store rm DIR NAME
+// store gc DIR
+// put creates the store on first use: encrypted if --key is given, with
+// --avg as the average chunk size. NAME defaults to the file name.
+fn command_store(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let usage = "usage: store put|get|list|rm|gc DIR ...";
+    let mut key = None;
+    let mut name = None;
+    let mut avg_size = None;
+    let mut positional = Vec::new();
+    
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--key" => key = Some(hex::decode(option_value(&mut args, arg)?)?),
+            "--name" => name = Some(option_value(&mut args, arg)?),
+            "--avg" => avg_size = Some(fastcdc::ChunkerConfig::new(parse_size(option_value(&mut args, arg)?)?)?),
+            _ => positional.push(arg.as_str()),
+        }
+    }
+    let (subcommand, dir, rest) = match positional.as_slice() {
+        [subcommand, dir, rest @ ..] => (*subcommand, Path::new(dir), rest),
+        _ => return Err(usage.into()),
+    };
+    
+    match (subcommand, rest) {
+        ("put", [file]) => {
+            let store = if dir.join("config").exists() {
+                let store = chunk_store::ChunkStore::open(dir, key.as_deref())?;
+                if avg_size.is_some_and(|avg_size| avg_size != store.chunker()) {
+                    return Err("--avg only applies when the store is created".into());
+                }
+                store
+            } else {
+                chunk_store::ChunkStore::create(dir, avg_size.unwrap_or_default(), key.as_deref())?
+            };
+            
+            let name = match name {
+                Some(name) => name.to_string(),
+                None if *file == "-" => return Err("--name is required for stdin".into()),
+                None => Path::new(file).file_name().ok_or("FILE has no file name")?.to_string_lossy().into_owned(),
+            };
+            let report = if *file == "-" {
+                store.put(&name, io::stdin().lock())?
+            } else {
+                store.put(&name, fs::File::open(file).map_err(|e| format!("{}: {}", file, e))?)?
+            };
+            println!("{}: {} bytes, {} chunks ({} new, {} bytes written)", name, report.size, report.chunks, report.new_chunks, report.new_bytes);
+        },
+        ("get", [name]) => {
+            let store = chunk_store::ChunkStore::open(dir, key.as_deref())?;
+            store.get(name, io::BufWriter::new(io::stdout().lock()))?;
+        },
+        ("list", []) => {
+            let store = chunk_store::ChunkStore::open(dir, None)?;
+            let files = store.list()?;
+            for file in &files {
+                println!("{:>12}  {:>6} chunks  {}", file.size, file.chunks, file.name);
+            }
+            
+            // Deduplication: bytes of all stored files vs bytes on disk
+            let total: u64 = files.iter().map(|file| file.size).sum();
+            let (objects, stored) = store.object_usage()?;
+            println!("{} file(s), {} bytes in {} chunk objects of {} bytes ({})", files.len(), total, objects, stored,
+                if store.is_encrypted() { "encrypted" } else { "not encrypted" });
+        },
+        ("rm", [name]) => {
+            chunk_store::ChunkStore::open(dir, None)?.remove(name)?;
+            println!("Removed {} (run gc to delete its chunks)", name);
+        },
+        ("gc", []) => {
+            let report = chunk_store::ChunkStore::open(dir, None)?.gc()?;
+            println!("Removed {} unreferenced chunk(s), {} bytes freed, {} kept", report.removed, report.freed_bytes, report.kept);
+        },
+        _ => return Err(usage.into()),
+    }
+    Ok(())
+}
+
+// Reads a whole file, or stdin for "-"
+fn read_input(file: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if file == "-" {
+        let mut data = Vec::new();
+        io::stdin().lock().read_to_end(&mut data)?;
+        Ok(data)
+    } else {
+        fs::read(file).map_err(|e| format!("{}: {}", file, e).into())
+    }
+}
+
+// mac --key HEX [--algo sha256|sha512] [FILE]
+// mac-verify --key HEX --tag HEX [--algo sha256|sha512] [FILE]
+// Verification prints OK or FAILED and fails (exit code 1) on mismatch
+fn command_mac(args: &[String], verify: bool) -> Result<(), Box<dyn Error>> {
+    let mut algorithm = mac::MacAlgorithm::HmacSha256;
+    let mut key = None;
+    let mut tag = None;
+    let mut file = "-";
+    
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--algo" => {
+                let name = option_value(&mut args, arg)?;
+                algorithm = mac::MacAlgorithm::from_name(name).ok_or_else(|| format!("unknown MAC algorithm: {}", name))?;
+            },
+            "--key" => key = Some(hex::decode(option_value(&mut args, arg)?)?),
+            "--tag" if verify => tag = Some(hex::decode(option_value(&mut args, arg)?)?),
+            _ => file = arg.as_str(),
+        }
+    }
+    
+    let key = key.ok_or("--key is required")?;
+    let data = read_input(file)?;
+    
+    if verify {
+        let tag = tag.ok_or("--tag is required")?;
+        if !algorithm.verify(&key, &data, &tag) {
+            println!("{}: FAILED", file);
+            return Err("HMAC tag mismatch".into());
+        }
+        println!("{}: OK", file);
+    } else {
+        println!("{}  {}", hex::encode(algorithm.compute(&key, &data)), file);
+    }
+    Ok(())
 }
 
 // Dedicated execution functions for each algorithm to simplify "all" mode handling
@@ -374,11 +1676,15 @@ fn run_aes_gcm(plain: &[u8], key_bytes: &[u8], nonce_bytes: &[u8]) {
     println!("and authentication of data. It's used in TLS, IPsec and other protocols.");
     
     let ciphertext = encrypt_aes_gcm(plain, key_bytes, nonce_bytes);
-    let decrypted_text = decrypt_aes_gcm(&ciphertext, key_bytes, nonce_bytes);
-    
     println!("Encrypted: {}", hex::encode(&ciphertext));
-    println!("Successful decryption");
-    println!("Decrypted: {}", String::from_utf8_lossy(&decrypted_text));
+    
+    match decrypt_aes_gcm(&ciphertext, key_bytes, nonce_bytes) {
+        Ok(decrypted_text) => {
+            println!("Successful decryption");
+            println!("Decrypted: {}", String::from_utf8_lossy(&decrypted_text));
+        },
+        Err(_) => println!("Decryption failed: authentication tag mismatch"),
+    }
 }
 
 fn run_chacha20poly1305(plain: &[u8], key_bytes: &[u8], nonce_bytes: &[u8]) {
@@ -388,11 +1694,65 @@ fn run_chacha20poly1305(plain: &[u8], key_bytes: &[u8], nonce_bytes: &[u8]) {
     println!("It's used in TLS 1.3, Wireguard and other modern protocols.");
     
     let ciphertext = encrypt_chacha20poly1305(plain, key_bytes, nonce_bytes);
-    let decrypted_text = decrypt_chacha20poly1305(&ciphertext, key_bytes, nonce_bytes);
-    
     println!("Encrypted: {}", hex::encode(&ciphertext));
-    println!("Successful decryption");
-    println!("Decrypted: {}", String::from_utf8_lossy(&decrypted_text));
+    
+    match decrypt_chacha20poly1305(&ciphertext, key_bytes, nonce_bytes) {
+        Ok(decrypted_text) => {
+            println!("Successful decryption");
+            println!("Decrypted: {}", String::from_utf8_lossy(&decrypted_text));
+        },
+        Err(_) => println!("Decryption failed: authentication tag mismatch"),
+    }
+}
+
+fn run_aes_cbc_hmac(plain: &[u8], key_bytes: &[u8]) {
+    println!("\n== AES-CBC + HMAC-SHA256 (legacy) ==");
+    println!("Description: AES-CBC is an older block cipher mode with PKCS#7 padding.");
+    println!("It is only offered with an HMAC-SHA256 tag (encrypt-then-MAC)");
+    println!("for compatibility with existing systems.");
+    
+    // Separate MAC key derived from the demo key, fixed demo IV (use a random IV in real use)
+    let mac_key = hash_sha256(key_bytes);
+    let iv = [0u8; 16];
+    
+    match legacy_modes::encrypt_aes_cbc_hmac(plain, key_bytes, &mac_key, &iv) {
+        Ok(ciphertext) => {
+            println!("Encrypted: {}", hex::encode(&ciphertext));
+            match legacy_modes::decrypt_aes_cbc_hmac(&ciphertext, key_bytes, &mac_key, &iv) {
+                Ok(decrypted_text) => {
+                    println!("Successful decryption");
+                    println!("Decrypted: {}", String::from_utf8_lossy(&decrypted_text));
+                },
+                Err(e) => println!("Decryption failed: {}", e),
+            }
+        },
+        Err(e) => println!("AES-CBC operation failed: {}", e),
+    }
+}
+
+fn run_aes_ctr_hmac(plain: &[u8], key_bytes: &[u8]) {
+    println!("\n== AES-CTR + HMAC-SHA256 (legacy) ==");
+    println!("Description: AES-CTR turns AES into a stream cipher using a counter.");
+    println!("It is only offered with an HMAC-SHA256 tag (encrypt-then-MAC)");
+    println!("for compatibility with existing systems.");
+    
+    // Separate MAC key derived from the demo key, fixed demo counter block (use a random one in real use)
+    let mac_key = hash_sha256(key_bytes);
+    let iv = [0u8; 16];
+    
+    match legacy_modes::encrypt_aes_ctr_hmac(plain, key_bytes, &mac_key, &iv) {
+        Ok(ciphertext) => {
+            println!("Encrypted: {}", hex::encode(&ciphertext));
+            match legacy_modes::decrypt_aes_ctr_hmac(&ciphertext, key_bytes, &mac_key, &iv) {
+                Ok(decrypted_text) => {
+                    println!("Successful decryption");
+                    println!("Decrypted: {}", String::from_utf8_lossy(&decrypted_text));
+                },
+                Err(e) => println!("Decryption failed: {}", e),
+            }
+        },
+        Err(e) => println!("AES-CTR operation failed: {}", e),
+    }
 }
 
 fn run_sha256(plain: &[u8]) {
@@ -405,6 +1765,83 @@ fn run_sha256(plain: &[u8]) {
     println!("Hash: {}", hex::encode(&hash));
 }
 
+fn run_sha224(plain: &[u8]) {
+    println!("\n== SHA-224 ==");
+    println!("Description: SHA-224 is SHA-256 with different initial values,");
+    println!("truncated to a 224-bit digest.");
+    
+    let hash = hash_sha224(plain);
+    println!("Hash: {}", hex::encode(&hash));
+}
+
+fn run_sha384(plain: &[u8]) {
+    println!("\n== SHA-384 ==");
+    println!("Description: SHA-384 is a truncated SHA-512 with its own initial values.");
+    println!("It's used in TLS certificates and Subresource Integrity (SRI).");
+    
+    let hash = hash_sha384(plain);
+    println!("Hash: {}", hex::encode(&hash));
+}
+
+fn run_sha512(plain: &[u8]) {
+    println!("\n== SHA-512 ==");
+    println!("Description: SHA-512 uses 64-bit words and produces a 512-bit digest.");
+    println!("It's often faster than SHA-256 on 64-bit processors.");
+    
+    let hash = hash_sha512(plain);
+    println!("Hash: {}", hex::encode(&hash));
+}
+
+fn run_sha512_224(plain: &[u8]) {
+    println!("\n== SHA-512/224 ==");
+    println!("Description: SHA-512/224 is SHA-512 truncated to 224 bits with its");
+    println!("own initial values. It resists length-extension attacks.");
+    
+    let hash = hash_sha512_224(plain);
+    println!("Hash: {}", hex::encode(&hash));
+}
+
+fn run_sha512_256(plain: &[u8]) {
+    println!("\n== SHA-512/256 ==");
+    println!("Description: SHA-512/256 is SHA-512 truncated to 256 bits with its");
+    println!("own initial values. It resists length-extension attacks.");
+    
+    let hash = hash_sha512_256(plain);
+    println!("Hash: {}", hex::encode(&hash));
+}
+
+fn run_sha3(plain: &[u8]) {
+    println!("\n== SHA-3 ==");
+    println!("Description: SHA-3 is based on the Keccak sponge construction,");
+    println!("a completely different design from SHA-2. It's the NIST");
+    println!("standard alternative in case SHA-2 is ever weakened.");
+    
+    println!("SHA3-224: {}", hex::encode(hash_sha3_224(plain)));
+    println!("SHA3-256: {}", hex::encode(hash_sha3_256(plain)));
+    println!("SHA3-384: {}", hex::encode(hash_sha3_384(plain)));
+    println!("SHA3-512: {}", hex::encode(hash_sha3_512(plain)));
+}
+
+fn run_shake(plain: &[u8], hash_options: &HashOptions) {
+    println!("\n== SHAKE ==");
+    println!("Description: SHAKE128 and SHAKE256 are the extendable-output");
+    println!("functions of SHA-3: they produce as many bytes as requested.");
+    
+    let length = hash_options.length;
+    println!("SHAKE128: {}", hex::encode(hash_shake128(plain, length.unwrap_or(32))));
+    println!("SHAKE256: {}", hex::encode(hash_shake256(plain, length.unwrap_or(64))));
+}
+
+fn run_keccak256(plain: &[u8]) {
+    println!("\n== Keccak-256 (Ethereum) ==");
+    println!("Description: Keccak-256 is the original Keccak hash used by Ethereum");
+    println!("for addresses and transaction hashes. Its padding differs from");
+    println!("the final SHA3-256 standard, so the outputs are different.");
+    
+    let hash = hash_keccak256(plain);
+    println!("Hash: {}", hex::encode(&hash));
+}
+
 fn run_double_sha256(plain: &[u8]) {
     println!("\n== Double SHA-256 (Bitcoin) ==");
     println!("Description: Double SHA-256 means applying SHA-256 twice");
@@ -415,35 +1852,148 @@ fn run_double_sha256(plain: &[u8]) {
     println!("Hash: {}", hex::encode(&hash));
 }
 
-fn run_blake2b(plain: &[u8]) {
+fn run_ripemd160(plain: &[u8]) {
+    println!("\n== RIPEMD-160 ==");
+    println!("Description: RIPEMD-160 is a 160-bit hash designed in Europe");
+    println!("independently from SHA. Bitcoin uses it to shorten addresses.");
+    
+    let hash = hash_ripemd160(plain);
+    println!("Hash: {}", hex::encode(&hash));
+}
+
+fn run_hash160(plain: &[u8]) {
+    println!("\n== HASH160 (Bitcoin) ==");
+    println!("Description: HASH160 means RIPEMD160(SHA256(message)). Bitcoin");
+    println!("addresses and scripts are identified by this 20-byte hash.");
+    
+    let hash = hash160(plain);
+    println!("Hash: {}", hex::encode(&hash));
+}
+
+fn run_tagged_hash(plain: &[u8]) {
+    println!("\n== Tagged hashes (BIP-340) ==");
+    println!("Description: A tagged hash is SHA256(SHA256(tag) || SHA256(tag) || message).");
+    println!("Taproot and Schnorr signatures use a different tag for each purpose,");
+    println!("so a hash can't be reused in another context. The hash state after");
+    println!("the 64-byte tag prefix is precomputed for the common tags.");
+    
+    for known in tagged_hash::KNOWN_TAGS {
+        println!("{:<18} {}", format!("{}:", known.name), hex::encode(tagged_hash::tagged_hash(known.name, plain)));
+    }
+}
+
+fn run_merkle() {
+    println!("\n== Bitcoin Merkle tree ==");
+    println!("Description: A block header commits to all its transactions with");
+    println!("a Merkle root built with double SHA-256. An inclusion proof lets a");
+    println!("light client check a transaction with only log2(n) hashes.");
+    
+    // Transactions of block 100000, as displayed by block explorers
+    let txids: Result<Vec<_>, _> = [
+        "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+        "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+        "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+        "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+    ].iter().map(|txid| merkle::hash_from_hex(txid)).collect();
+    let txids = match txids {
+        Ok(txids) => txids,
+        Err(e) => {
+            println!("Invalid txid: {}", e);
+            return;
+        }
+    };
+    
+    let root = merkle::merkle_root(&txids).expect("block has transactions");
+    println!("Block 100000 Merkle root: {}", merkle::hash_to_hex(&root));
+    
+    // Prove that the third transaction is in the block
+    let index = 2;
+    let proof = merkle::merkle_proof(&txids, index).expect("index is in the block");
+    println!("Proof for transaction {}:", index);
+    for sibling in &proof.siblings {
+        println!("  {}", merkle::hash_to_hex(sibling));
+    }
+    let valid = merkle::verify_proof(&txids[index], &proof, &root);
+    println!("Verification: {}", if valid { "Success" } else { "Failed" });
+}
+
+fn run_blake2b(plain: &[u8], hash_options: &HashOptions) {
     println!("\n== Blake2b ==");
     println!("Description: Blake2b is a cryptographic hash function");
     println!("optimized for 64-bit platforms with a 512-bit output.");
     println!("It's faster than MD5, SHA-1, SHA-2, and SHA-3, while offering");
     println!("high security.");
     
-    let hash = hash_blake2b(plain);
-    println!("Hash: {}", hex::encode(&hash));
+    if hash_options.has_blake2_params() {
+        let key = hash_options.key.as_deref().unwrap_or_default();
+        let length = hash_options.length.unwrap_or(64);
+        match hash_blake2b_keyed(plain, key, &hash_options.salt, &hash_options.personal, length) {
+            Ok(hash) => println!("Keyed hash ({} bytes): {}", length, hex::encode(&hash)),
+            Err(e) => println!("Blake2b operation failed: {}", e),
+        }
+        return;
+    }
+    
+    match hash_options.length {
+        Some(length) => match hash_blake2b_var(plain, length) {
+            Ok(hash) => println!("Hash ({} bytes): {}", length, hex::encode(&hash)),
+            Err(e) => println!("Blake2b operation failed: {}", e),
+        },
+        None => println!("Hash: {}", hex::encode(hash_blake2b(plain))),
+    }
 }
 
-fn run_blake2s(plain: &[u8]) {
+fn run_blake2s(plain: &[u8], hash_options: &HashOptions) {
     println!("\n== Blake2s ==");
     println!("Description: Blake2s is a variant of Blake2 optimized for");
     println!("32-bit platforms with a 256-bit output.");
     println!("Ideal for embedded systems and resource-constrained devices.");
     
-    let hash = hash_blake2s(plain);
-    println!("Hash: {}", hex::encode(&hash));
+    if hash_options.has_blake2_params() {
+        let key = hash_options.key.as_deref().unwrap_or_default();
+        let length = hash_options.length.unwrap_or(32);
+        match hash_blake2s_keyed(plain, key, &hash_options.salt, &hash_options.personal, length) {
+            Ok(hash) => println!("Keyed hash ({} bytes): {}", length, hex::encode(&hash)),
+            Err(e) => println!("Blake2s operation failed: {}", e),
+        }
+        return;
+    }
+    
+    match hash_options.length {
+        Some(length) => match hash_blake2s_var(plain, length) {
+            Ok(hash) => println!("Hash ({} bytes): {}", length, hex::encode(&hash)),
+            Err(e) => println!("Blake2s operation failed: {}", e),
+        },
+        None => println!("Hash: {}", hex::encode(hash_blake2s(plain))),
+    }
 }
 
-fn run_blake3(plain: &[u8]) {
+fn run_blake3(plain: &[u8], hash_options: &HashOptions) {
     println!("\n== Blake3 ==");
     println!("Description: Blake3 is the latest algorithm in the Blake family,");
     println!("designed to be extremely fast with enhanced security.");
     println!("It's parallelizable and ideal for hashing large files.");
     
-    let hash = hash_blake3(plain);
-    println!("Hash: {}", hex::encode(&hash));
+    let length = hash_options.length.unwrap_or(32);
+    if let Some(context) = &hash_options.context {
+        // The message is the key material
+        if hash_options.key.is_some() || hash_options.offset > 0 {
+            println!("Blake3 operation failed: --context can't be combined with --key or --offset");
+        } else {
+            println!("Context: {:?}", context);
+            println!("Derived key ({} bytes): {}", length, hex::encode(derive_key_blake3(context, plain, length)));
+        }
+    } else if let Some(key) = &hash_options.key {
+        match hash_blake3_keyed(plain, key, length, hash_options.offset) {
+            Ok(hash) => println!("Keyed hash ({} bytes at offset {}): {}", length, hash_options.offset, hex::encode(&hash)),
+            Err(e) => println!("Blake3 operation failed: {}", e),
+        }
+    } else if hash_options.length.is_some() || hash_options.offset > 0 {
+        let hash = hash_blake3_xof(plain, length, hash_options.offset);
+        println!("Hash ({} bytes at offset {}): {}", length, hash_options.offset, hex::encode(&hash));
+    } else {
+        println!("Hash: {}", hex::encode(hash_blake3(plain)));
+    }
 }
 
 fn run_ed25519(plain: &[u8]) {
@@ -475,9 +2025,364 @@ fn run_secp256k1(plain: &[u8]) {
             println!("Secret key: {}", hex::encode(&secret_key));
             println!("Public key: {}", hex::encode(&public_key));
             println!("Verification: {}", if verification { "Success" } else { "Failed" });
+            
+            // Identifier used in Bitcoin addresses (P2PKH / P2WPKH)
+            if let Ok(public_key) = secp256k1::PublicKey::from_slice(&public_key) {
+                println!("HASH160 (compressed key): {}", hex::encode(hash160_public_key(&public_key, true)));
+                println!("HASH160 (uncompressed key): {}", hex::encode(hash160_public_key(&public_key, false)));
+            }
         },
         Err(e) => {
             println!("Secp256k1 operation failed: {}", e);
         }
     }
-}
\ No newline at end of file
+}
+
+fn run_committing(plain: &[u8], key_bytes: &[u8], nonce_bytes: &[u8]) {
+    println!("\n== Key-committing AEAD ==");
+    println!("Description: A key commitment derived with HKDF-SHA256 is prepended to");
+    println!("the ciphertext, so a ciphertext can only ever decrypt under one key.");
+    println!("This prevents partitioning-oracle attacks on AES-GCM and ChaCha20-Poly1305.");
+    
+    // Any other key must be rejected by the commitment check
+    let mut wrong_key = key_bytes.to_vec();
+    wrong_key[0] ^= 1;
+    
+    for cipher in [Cipher::AesGcm, Cipher::ChaCha20Poly1305] {
+        let ciphertext = committing::encrypt_committing(cipher, plain, key_bytes, nonce_bytes);
+        
+        println!("\nCipher: {}", cipher.name());
+        println!("Encrypted: {}", hex::encode(&ciphertext));
+        match committing::decrypt_committing(cipher, &ciphertext, key_bytes, nonce_bytes) {
+            Ok(decrypted_text) => println!("Decrypted: {}", String::from_utf8_lossy(&decrypted_text)),
+            Err(e) => println!("Decryption failed: {}", e),
+        }
+        match committing::decrypt_committing(cipher, &ciphertext, &wrong_key, nonce_bytes) {
+            Ok(_) => println!("Other key: accepted (unexpected!)"),
+            Err(e) => println!("Other key: rejected ({})", e),
+        }
+    }
+}
+
+fn run_envelope(plain: &[u8], key_bytes: &[u8]) {
+    println!("\n== Envelope Encryption ==");
+    println!("Description: The message is encrypted with a random data key (DEK),");
+    println!("and only the DEK is encrypted with a named master key (KEK).");
+    println!("Rotating the master key rewraps the DEK without re-encrypting the data.");
+    
+    // Old master key is the demo key, new master key is random
+    let mut keyring = envelope::KeyRing::new();
+    let mut new_kek = [0u8; 32];
+    rand::RngCore::fill_bytes(&mut rand::rng(), &mut new_kek);
+    if let Err(e) = keyring.add("master-v1", key_bytes).and_then(|_| keyring.add("master-v2", &new_kek)) {
+        println!("Envelope operation failed: {}", e);
+        return;
+    }
+    
+    for cipher in [Cipher::AesGcm, Cipher::ChaCha20Poly1305] {
+        let result = envelope::seal(&keyring, "master-v1", cipher, plain).and_then(|sealed| {
+            let rotated = envelope::rotate(&keyring, &sealed, "master-v2")?;
+            let decrypted = envelope::open(&keyring, &rotated)?;
+            Ok((sealed, rotated, decrypted))
+        });
+        
+        println!("\nCipher: {}", cipher.name());
+        match result {
+            Ok((sealed, rotated, decrypted)) => {
+                println!("Encrypted: {}", hex::encode(&sealed.ciphertext));
+                println!("Wrapped DEK ({}): {}", sealed.kek_id, hex::encode(&sealed.wrapped_dek));
+                println!("Wrapped DEK ({}): {}", rotated.kek_id, hex::encode(&rotated.wrapped_dek));
+                println!("Payload unchanged by rotation: {}", sealed.ciphertext == rotated.ciphertext);
+                println!("Decrypted: {}", String::from_utf8_lossy(&decrypted));
+            },
+            Err(e) => {
+                println!("Envelope operation failed: {}", e);
+            }
+        }
+    }
+}
+
+fn run_nonce_counter(plain: &[u8], key_bytes: &[u8]) {
+    println!("\n== Nonce-reuse protection ==");
+    println!("Description: The encryptor picks nonces from a per-key counter saved");
+    println!("to a state file, so a nonce is never repeated, even across runs.");
+    println!("It refuses to encrypt once the AES-GCM per-key limit (2^32) is reached.");
+    
+    // Counters persist between runs of the demo
+    let state_path = env::temp_dir().join("rust-cryptocraphy-nonces.txt");
+    println!("State file: {}", state_path.display());
+    
+    let encryptor = match nonce_counter::StatefulEncryptor::new(Cipher::AesGcm, key_bytes, &state_path) {
+        Ok(encryptor) => encryptor,
+        Err(e) => {
+            println!("Nonce state loading failed: {}", e);
+            return;
+        }
+    };
+    match encryptor.messages_encrypted() {
+        Ok(count) => println!("Messages already encrypted with this key: {}", count),
+        Err(e) => println!("Nonce state loading failed: {}", e),
+    }
+    
+    for _ in 0..3 {
+        match encryptor.encrypt(plain) {
+            Ok((nonce, ciphertext)) => {
+                println!("Nonce: {} Encrypted: {}", hex::encode(nonce), hex::encode(&ciphertext));
+                if let Ok(decrypted_text) = decrypt_aes_gcm(&ciphertext, key_bytes, &nonce) {
+                    println!("Decrypted: {}", String::from_utf8_lossy(&decrypted_text));
+                }
+            },
+            Err(e) => {
+                println!("Encryption refused: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+fn run_password(plain: &[u8]) {
+    println!("\n== Password hashing ==");
+    println!("Description: Argon2id, scrypt and bcrypt are slow, salted hashes made");
+    println!("for storing passwords. The stored string holds the algorithm, costs");
+    println!("and salt, so it can be verified later and upgraded when costs change.");
+    
+    // scrypt with N = 2^14 (16 MiB) instead of 2^17 to keep the demo quick
+    let mut policy = password::PasswordPolicy {
+        scrypt: scrypt::Params::new(14, 8, 1, scrypt::Params::RECOMMENDED_LEN).expect("valid scrypt parameters"),
+        ..Default::default()
+    };
+    
+    for algorithm in [password::PasswordAlgorithm::Argon2id, password::PasswordAlgorithm::Scrypt, password::PasswordAlgorithm::Bcrypt] {
+        policy.algorithm = algorithm;
+        let start = Instant::now();
+        let hash = match password::hash_password(plain, &policy) {
+            Ok(hash) => hash,
+            Err(e) => {
+                println!("\n{:?}: hashing failed: {}", algorithm, e);
+                continue;
+            }
+        };
+        let elapsed = start.elapsed();
+        
+        println!("\n{:?} ({:.0} ms): {}", algorithm, elapsed.as_secs_f64() * 1000.0, hash);
+        let verified = password::verify_password(plain, &hash).unwrap_or(false);
+        println!("Correct password: {}", if verified { "Success" } else { "Failed" });
+        let rejected = !password::verify_password(b"wrong password", &hash).unwrap_or(true);
+        println!("Wrong password rejected: {}", if rejected { "Success" } else { "Failed" });
+    }
+    
+    // The policy moved to Argon2id: the last (bcrypt) hash should be replaced at next login
+    let bcrypt_hash = password::hash_password(plain, &policy);
+    policy.algorithm = password::PasswordAlgorithm::Argon2id;
+    match bcrypt_hash.and_then(|hash| password::needs_rehash(&hash, &policy)) {
+        Ok(needs_rehash) => println!("\nbcrypt hash needs rehash under an Argon2id policy: {}", needs_rehash),
+        Err(e) => println!("\nRehash check failed: {}", e),
+    }
+}
+
+fn run_session(plain: &[u8], key_bytes: &[u8], nonce_bytes: &[u8]) {
+    println!("\n== Reusable cipher sessions ==");
+    println!("Description: A session expands the key once and reuses it for every");
+    println!("message, instead of rebuilding the cipher on each call. This matters");
+    println!("most for small messages (use `cargo run --release` for real timings).");
+    
+    const ITERATIONS: u32 = 20_000;
+    
+    for cipher in [Cipher::AesGcm, Cipher::ChaCha20Poly1305] {
+        let session = match session::AeadSession::new(cipher, key_bytes) {
+            Ok(session) => session,
+            Err(e) => {
+                println!("Session creation failed: {}", e);
+                return;
+            }
+        };
+        
+        // Per-call functions: new cipher (and key schedule) for every message
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            black_box(cipher.encrypt(black_box(plain), key_bytes, nonce_bytes));
+        }
+        let per_call = start.elapsed();
+        
+        // Session: key schedule computed once above
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            let _ = black_box(session.encrypt(black_box(plain), nonce_bytes));
+        }
+        let reused = start.elapsed();
+        
+        println!("\nCipher: {} ({} messages of {} bytes)", cipher.name(), ITERATIONS, plain.len());
+        println!("Per-call key setup: {:.0} ns/message", per_call.as_nanos() as f64 / ITERATIONS as f64);
+        println!("Reusable session:   {:.0} ns/message", reused.as_nanos() as f64 / ITERATIONS as f64);
+        println!("Speedup: {:.2}x", per_call.as_secs_f64() / reused.as_secs_f64());
+        
+        match session.encrypt(plain, nonce_bytes).and_then(|ciphertext| session.decrypt(&ciphertext, nonce_bytes)) {
+            Ok(decrypted_text) => println!("Decrypted: {}", String::from_utf8_lossy(&decrypted_text)),
+            Err(e) => println!("Session operation failed: {}", e),
+        }
+    }
+}
+
+fn run_chunking(plain: &[u8]) {
+    println!("\n== Content-defined chunking (FastCDC) ==");
+    println!("Description: FastCDC cuts data where a rolling hash of the last bytes");
+    println!("hits a pattern, so boundaries follow the content. After an insertion,");
+    println!("only the chunks around it change: a backup store addressed by chunk");
+    println!("hashes only has to store those.");
+    
+    // 1 MiB of pseudo-random data, then the same data with the message inserted
+    let config = fastcdc::ChunkerConfig::new(8 * 1024).expect("valid chunk size");
+    let data = hash_blake3_xof(b"chunking demo", 1024 * 1024, 0);
+    let mut edited = data.clone();
+    edited.splice(300_000..300_000, plain.iter().copied());
+    
+    let original: Vec<Vec<u8>> = fastcdc::chunks(&data, config).map(hash_blake3).collect();
+    let edited_chunks: Vec<&[u8]> = fastcdc::chunks(&edited, config).collect();
+    let new_chunks: Vec<&&[u8]> = edited_chunks.iter().filter(|chunk| !original.contains(&hash_blake3(chunk))).collect();
+    
+    println!("\nChunk sizes: {} min, {} average, {} max", config.min_size(), config.avg_size(), config.max_size());
+    println!("Original: {} bytes, {} chunks", data.len(), original.len());
+    println!("With the message inserted at offset 300000: {} chunks", edited_chunks.len());
+    println!("New chunks to store: {} ({} bytes)", new_chunks.len(), new_chunks.iter().map(|chunk| chunk.len()).sum::<usize>());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // NIST FIPS 202 examples: "abc" and the 1600-bit message of repeated 0xa3 bytes
+    const NIST_ABC: &[u8] = b"abc";
+    const NIST_A3_1600: [u8; 200] = [0xa3; 200];
+    
+    #[test]
+    fn sha3_nist_vectors() {
+        assert_eq!(hex::encode(hash_sha3_224(NIST_ABC)), "e642824c3f8cf24ad09234ee7d3c766fc9a3a5168d0c94ad73b46fdf");
+        assert_eq!(hex::encode(hash_sha3_256(NIST_ABC)), "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532");
+        assert_eq!(hex::encode(hash_sha3_384(NIST_ABC)), "ec01498288516fc926459f58e2c6ad8df9b473cb0fc08c2596da7cf0e49be4b298d88cea927ac7f539f1edf228376d25");
+        assert_eq!(hex::encode(hash_sha3_512(NIST_ABC)), "b751850b1a57168a5693cd924b6b096e08f621827444f70d884f5d0240d2712e10e116e9192af3c91a7ec57647e3934057340b4cf408d5a56592f8274eec53f0");
+        
+        assert_eq!(hex::encode(hash_sha3_224(&NIST_A3_1600)), "9376816aba503f72f96ce7eb65ac095deee3be4bf9bbc2a1cb7e11e0");
+        assert_eq!(hex::encode(hash_sha3_256(&NIST_A3_1600)), "79f38adec5c20307a98ef76e8324afbfd46cfd81b22e3973c65fa1bd9de31787");
+        assert_eq!(hex::encode(hash_sha3_384(&NIST_A3_1600)), "1881de2ca7e41ef95dc4732b8f5f002b189cc1e42b74168ed1732649ce1dbcdd76197a31fd55ee989f2d7050dd473e8f");
+        assert_eq!(hex::encode(hash_sha3_512(&NIST_A3_1600)), "e76dfad22084a8b1467fcf2ffa58361bec7628edf5f3fdc0e4805dc48caeeca81b7c13c30adf52a3659584739a2df46be589c51ca1a4a8416df6545a1ce8ba00");
+    }
+    
+    #[test]
+    fn shake_nist_vectors() {
+        assert_eq!(hex::encode(hash_shake128(b"", 32)), "7f9c2ba4e88f827d616045507605853ed73b8093f6efbc88eb1a6eacfa66ef26");
+        assert_eq!(hex::encode(hash_shake256(b"", 64)), "46b9dd2b0ba88d13233b3feb743eeb243fcd52ea62b81b82b50c27646ed5762fd75dc4ddd8c0f200cb05019d67b592f6fc821c49479ab48640292eacb3b7c4be");
+        assert_eq!(hex::encode(hash_shake128(&NIST_A3_1600, 32)), "131ab8d2b594946b9c81333f9bb6e0ce75c3b93104fa3469d3917457385da037");
+        assert_eq!(hex::encode(hash_shake256(&NIST_A3_1600, 64)), "cd8a920ed141aa0407a22d59288652e9d9f1a7ee0c1e7c1ca699424da84a904d2d700caae7396ece96604440577da4f3aa22aeb8857f961c4cd8e06f0ae6610b");
+        
+        // Shorter outputs are prefixes of longer ones
+        assert_eq!(hash_shake256(NIST_ABC, 16), hash_shake256(NIST_ABC, 64)[..16]);
+    }
+    
+    #[test]
+    fn ripemd160_vectors() {
+        assert_eq!(hex::encode(hash_ripemd160(b"")), "9c1185a5c5e9fc54612808977ee8f548b2258d31");
+        assert_eq!(hex::encode(hash_ripemd160(NIST_ABC)), "8eb208f7e05d987a9b044a8e98c6b087f15a0bfc");
+    }
+    
+    #[test]
+    fn hash160_of_generator_public_key() {
+        // Private key 1: its public key is the curve generator G
+        let secp = Secp256k1::new();
+        let mut secret_key_bytes = [0u8; 32];
+        secret_key_bytes[31] = 1;
+        let secret_key = secp256k1::SecretKey::from_slice(&secret_key_bytes).unwrap();
+        let public_key = secp256k1::PublicKey::from_secret_key(&secp, &secret_key);
+        
+        // bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4 / 1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH
+        assert_eq!(hex::encode(hash160_public_key(&public_key, true)), "751e76e8199196d454941c45d1b3a323f1433bd6");
+        // 1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm
+        assert_eq!(hex::encode(hash160_public_key(&public_key, false)), "91b24bf9f5288532960ac687abb035127b1d28a5");
+    }
+    
+    #[test]
+    fn keccak256_differs_from_sha3_256() {
+        assert_eq!(hex::encode(hash_keccak256(b"")), "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+        assert_eq!(hex::encode(hash_keccak256(NIST_ABC)), "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45");
+        assert_ne!(hash_keccak256(NIST_ABC), hash_sha3_256(NIST_ABC));
+    }
+    
+    // Blake2 with salt and personalization. The persona vectors come from the
+    // RustCrypto blake2 tests, the others from the reference implementation
+    // (Python hashlib) with key, salt and personalization all set.
+    #[test]
+    fn blake2_salt_and_personal_vectors() {
+        let key: Vec<u8> = (0..64).collect();
+        let message: Vec<u8> = (0..255).collect();
+        
+        let digest = hash_blake2b_keyed(b"", &key[..32], b"", b"personal", 64).unwrap();
+        assert_eq!(hex::encode(digest), "03de3b295dcfc3b25b05abb09bc95fe3e9ff3073638badc68101d1e42019d0771dd07525a3aae8318e92c5e5d967ba92e4810d0021d7bf3b49da0b4b4a8a4e1f");
+        let digest = hash_blake2s_keyed(b"", &key[..32], b"", b"personal", 32).unwrap();
+        assert_eq!(hex::encode(digest), "25a4ee63b594aed3f88a971e1877ef7099534f9097291f88fb86c79b5e70d022");
+        
+        let digest = hash_blake2b_keyed(&message, &key, &key[..16], &key[16..32], 64).unwrap();
+        assert_eq!(hex::encode(digest), "c8ec6a642fa69cadb1a351b57bce9fbd1cbbdc95e047f3c46d0279dc4707d1a529a462ef4e2fa4bd7e3f75717648a74d546b26b6664285e04b0a2074ab9c3a5e");
+        let digest = hash_blake2s_keyed(&message, &key[..32], &key[..8], &key[8..16], 32).unwrap();
+        assert_eq!(hex::encode(digest), "69c6f6a66deecaf505830f44237ee2f2e853d46559b1b2d20bfd4854ac96fa21");
+        
+        // Unkeyed, with a shorter output (the length is part of the parameter block)
+        let digest = hash_blake2b_keyed(NIST_ABC, b"", b"saltsaltsaltsalt", b"personalpersonal", 32).unwrap();
+        assert_eq!(hex::encode(digest), "bbe90f00caca77841069c830aa922ad3b44f91adfd88bc5f69b3e9c1c4d9b780");
+        let digest = hash_blake2s_keyed(NIST_ABC, b"", b"saltsalt", b"personal", 16).unwrap();
+        assert_eq!(hex::encode(digest), "4520a0fe6e789c22b8a7657529949a08");
+        
+        assert!(hash_blake2s_keyed(NIST_ABC, b"", b"too long salt", b"", 32).is_err());
+    }
+    
+    // Official BLAKE3 test_vectors.json: input byte i is i % 251, 131-byte outputs
+    const BLAKE3_TEST_KEY: &[u8; 32] = b"whats the Elvish word for friend";
+    const BLAKE3_TEST_CONTEXT: &str = "BLAKE3 2019-12-27 16:29:52 test vectors context";
+    const BLAKE3_VECTORS: [(usize, &str, &str); 6] = [
+        (
+            0,
+            "92b2b75604ed3c761f9d6f62392c8a9227ad0ea3f09573e783f1498a4ed60d26b18171a2f22a4b94822c701f107153dba24918c4bae4d2945c20ece13387627d3b73cbf97b797d5e59948c7ef788f54372df45e45e4293c7dc18c1d41144a9758be58960856be1eabbe22c2653190de560ca3b2ac4aa692a9210694254c371e851bc8f",
+            "2cc39783c223154fea8dfb7c1b1660f2ac2dcbd1c1de8277b0b0dd39b7e50d7d905630c8be290dfcf3e6842f13bddd573c098c3f17361f1f206b8cad9d088aa4a3f746752c6b0ce6a83b0da81d59649257cdf8eb3e9f7d4998e41021fac119deefb896224ac99f860011f73609e6e0e4540f93b273e56547dfd3aa1a035ba6689d89a0",
+        ),
+        (
+            1,
+            "6d7878dfff2f485635d39013278ae14f1454b8c0a3a2d34bc1ab38228a80c95b6568c0490609413006fbd428eb3fd14e7756d90f73a4725fad147f7bf70fd61c4e0cf7074885e92b0e3f125978b4154986d4fb202a3f331a3fb6cf349a3a70e49990f98fe4289761c8602c4e6ab1138d31d3b62218078b2f3ba9a88e1d08d0dd4cea11",
+            "b3e2e340a117a499c6cf2398a19ee0d29cca2bb7404c73063382693bf66cb06c5827b91bf889b6b97c5477f535361caefca0b5d8c4746441c57617111933158950670f9aa8a05d791daae10ac683cbef8faf897c84e6114a59d2173c3f417023a35d6983f2c7dfa57e7fc559ad751dbfb9ffab39c2ef8c4aafebc9ae973a64f0c76551",
+        ),
+        (
+            1023,
+            "c951ecdf03288d0fcc96ee3413563d8a6d3589547f2c2fb36d9786470f1b9d6e890316d2e6d8b8c25b0a5b2180f94fb1a158ef508c3cde45e2966bd796a696d3e13efd86259d756387d9becf5c8bf1ce2192b87025152907b6d8cc33d17826d8b7b9bc97e38c3c85108ef09f013e01c229c20a83d9e8efac5b37470da28575fd755a10",
+            "74a16c1c3d44368a86e1ca6df64be6a2f64cce8f09220787450722d85725dea59c413264404661e9e4d955409dfe4ad3aa487871bcd454ed12abfe2c2b1eb7757588cf6cb18d2eccad49e018c0d0fec323bec82bf1644c6325717d13ea712e6840d3e6e730d35553f59eff5377a9c350bcc1556694b924b858f329c44ee64b884ef00d",
+        ),
+        (
+            1024,
+            "75c46f6f3d9eb4f55ecaaee480db732e6c2105546f1e675003687c31719c7ba4a78bc838c72852d4f49c864acb7adafe2478e824afe51c8919d06168414c265f298a8094b1ad813a9b8614acabac321f24ce61c5a5346eb519520d38ecc43e89b5000236df0597243e4d2493fd626730e2ba17ac4d8824d09d1a4a8f57b8227778e2de",
+            "7356cd7720d5b66b6d0697eb3177d9f8d73a4a5c5e968896eb6a6896843027066c23b601d3ddfb391e90d5c8eccdef4ae2a264bce9e612ba15e2bc9d654af1481b2e75dbabe615974f1070bba84d56853265a34330b4766f8e75edd1f4a1650476c10802f22b64bd3919d246ba20a17558bc51c199efdec67e80a227251808d8ce5bad",
+        ),
+        (
+            1025,
+            "357dc55de0c7e382c900fd6e320acc04146be01db6a8ce7210b7189bd664ea69362396b77fdc0d2634a552970843722066c3c15902ae5097e00ff53f1e116f1cd5352720113a837ab2452cafbde4d54085d9cf5d21ca613071551b25d52e69d6c81123872b6f19cd3bc1333edf0c52b94de23ba772cf82636cff4542540a7738d5b930",
+            "effaa245f065fbf82ac186839a249707c3bddf6d3fdda22d1b95a3c970379bcb5d31013a167509e9066273ab6e2123bc835b408b067d88f96addb550d96b6852dad38e320b9d940f86db74d398c770f462118b35d2724efa13da97194491d96dd37c3c09cbef665953f2ee85ec83d88b88d11547a6f911c8217cca46defa2751e7f3ad",
+        ),
+        (
+            8193,
+            "954a2a75420c8d6547e3ba5b98d963e6fa6491addc8c023189cc519821b4a1f5f03228648fd983aef045c2fa8290934b0866b615f585149587dda2299039965328835a2b18f1d63b7e300fc76ff260b571839fe44876a4eae66cbac8c67694411ed7e09df51068a22c6e67d6d3dd2cca8ff12e3275384006c80f4db68023f24eebba57",
+            "af1e0346e389b17c23200270a64aa4e1ead98c61695d917de7d5b00491c9b0f12f20a01d6d622edf3de026a4db4e4526225debb93c1237934d71c7340bb5916158cbdafe9ac3225476b6ab57a12357db3abbad7a26c6e66290e44034fb08a20a8d0ec264f309994d2810c49cfba6989d7abb095897459f5425adb48aba07c5fb3c83c0",
+        ),
+    ];
+    
+    #[test]
+    fn blake3_keyed_and_derive_key_vectors() {
+        for (input_len, keyed_hash, derive_key) in BLAKE3_VECTORS {
+            let input: Vec<u8> = (0..input_len).map(|i| (i % 251) as u8).collect();
+            
+            assert_eq!(hex::encode(hash_blake3_keyed(&input, BLAKE3_TEST_KEY, 131, 0).unwrap()), keyed_hash, "keyed_hash, input_len {}", input_len);
+            assert_eq!(hex::encode(derive_key_blake3(BLAKE3_TEST_CONTEXT, &input, 131)), derive_key, "derive_key, input_len {}", input_len);
+            
+            // Default 32-byte output and reads from an XOF offset
+            assert_eq!(hex::encode(hash_blake3_keyed(&input, BLAKE3_TEST_KEY, 32, 0).unwrap()), keyed_hash[..64]);
+            assert_eq!(hex::encode(hash_blake3_keyed(&input, BLAKE3_TEST_KEY, 67, 64).unwrap()), keyed_hash[128..]);
+            assert_eq!(hex::encode(derive_key_blake3(BLAKE3_TEST_CONTEXT, &input, 32)), derive_key[..64]);
+        }
+        
+        assert!(hash_blake3_keyed(b"", &BLAKE3_TEST_KEY[..31], 32, 0).is_err());
+    }
+}
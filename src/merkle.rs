@@ -0,0 +1,198 @@
+// ====================================================
+// BITCOIN MERKLE TREES
+// ====================================================
+// Every Bitcoin block header commits to all the transactions of the block
+// through a single 32-byte Merkle root.
+//
+// How it works:
+// 1. The leaves are the transaction ids (txid = double SHA-256 of the
+//    serialized transaction)
+// 2. Each level hashes pairs of nodes: double SHA-256(left || right)
+// 3. When a level has an odd number of nodes, the last one is paired
+//    with itself (duplicated)
+// 4. The single remaining node is the Merkle root
+//
+// An inclusion proof (used by SPV light wallets) is the list of sibling
+// hashes on the path from a leaf to the root: log2(n) hashes instead of
+// the whole block.
+//
+// Byte order: hashes are computed on the internal byte order, but txids
+// and roots are displayed reversed (little-endian numbers), as in block
+// explorers and bitcoind.
+//
+// Warning: the duplication rule means [a, b, c] and [a, b, c, c] have the
+// same root (CVE-2012-2459); a validator must reject duplicate txids.
+
+use std::error::Error;
+
+use crate::hash_double_sha256;
+
+pub type Hash256 = [u8; 32];
+
+// Parses a txid (or block hash) as displayed by explorers into internal byte order
+pub fn hash_from_hex(display_hex: &str) -> Result<Hash256, Box<dyn Error>> {
+    let mut hash: Hash256 = hex::decode(display_hex)?
+        .try_into()
+        .map_err(|_| "a hash must be 32 bytes")?;
+    hash.reverse();
+    Ok(hash)
+}
+
+// Formats a hash in display order (reversed)
+pub fn hash_to_hex(hash: &Hash256) -> String {
+    let mut display = *hash;
+    display.reverse();
+    hex::encode(display)
+}
+
+// Parent node: double SHA-256 of the two children concatenated
+fn hash_pair(left: &Hash256, right: &Hash256) -> Hash256 {
+    let mut concat = [0u8; 64];
+    concat[..32].copy_from_slice(left);
+    concat[32..].copy_from_slice(right);
+    hash_double_sha256(&concat)
+        .try_into()
+        .expect("SHA-256 output is 32 bytes")
+}
+
+// Next level of the tree, duplicating the last node if the count is odd
+fn parent_level(level: &[Hash256]) -> Vec<Hash256> {
+    level
+        .chunks(2)
+        .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+        .collect()
+}
+
+// Merkle root of a block's transactions (None for an empty list).
+// A block with a single transaction has that txid as its root.
+pub fn merkle_root(txids: &[Hash256]) -> Option<Hash256> {
+    let mut level = txids.to_vec();
+    while level.len() > 1 {
+        level = parent_level(&level);
+    }
+    level.first().copied()
+}
+
+// Sibling hashes from the leaf up to (not including) the root
+pub struct MerkleProof {
+    pub index: usize,
+    pub siblings: Vec<Hash256>,
+}
+
+// Inclusion proof of the transaction at `index`
+pub fn merkle_proof(txids: &[Hash256], index: usize) -> Option<MerkleProof> {
+    if index >= txids.len() {
+        return None;
+    }
+
+    let mut siblings = Vec::new();
+    let mut level = txids.to_vec();
+    let mut position = index;
+    while level.len() > 1 {
+        // The last node of an odd level is its own sibling
+        let sibling = (position ^ 1).min(level.len() - 1);
+        siblings.push(level[sibling]);
+        level = parent_level(&level);
+        position /= 2;
+    }
+
+    Some(MerkleProof { index, siblings })
+}
+
+// Recomputes the root from a txid and its proof. The bits of the index
+// say whether the current node is a left (0) or right (1) child.
+pub fn verify_proof(txid: &Hash256, proof: &MerkleProof, root: &Hash256) -> bool {
+    // An index with more bits than the path is ambiguous: reject it
+    if proof.index.checked_shr(proof.siblings.len() as u32).unwrap_or(0) != 0 {
+        return false;
+    }
+
+    let mut node = *txid;
+    for (level, sibling) in proof.siblings.iter().enumerate() {
+        node = if (proof.index >> level) & 1 == 0 {
+            hash_pair(&node, sibling)
+        } else {
+            hash_pair(sibling, &node)
+        };
+    }
+    node == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Block 100000 (000000000003ba27aa200b1cecaad478d2b00432346c3f1f3986da1afd33e506)
+    const BLOCK_100000_TXIDS: [&str; 4] = [
+        "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+        "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+        "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+        "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+    ];
+    const BLOCK_100000_ROOT: &str = "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766";
+
+    // Genesis block: a single coinbase transaction
+    const GENESIS_TXID: &str = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+
+    fn block_100000_txids() -> Vec<Hash256> {
+        BLOCK_100000_TXIDS.iter().map(|txid| hash_from_hex(txid).unwrap()).collect()
+    }
+
+    #[test]
+    fn root_of_real_blocks() {
+        let root = merkle_root(&block_100000_txids()).unwrap();
+        assert_eq!(hash_to_hex(&root), BLOCK_100000_ROOT);
+
+        let genesis = hash_from_hex(GENESIS_TXID).unwrap();
+        assert_eq!(hash_to_hex(&merkle_root(&[genesis]).unwrap()), GENESIS_TXID);
+
+        assert!(merkle_root(&[]).is_none());
+    }
+
+    #[test]
+    fn odd_levels_duplicate_the_last_node() {
+        let txids = block_100000_txids();
+        let (a, b, c) = (txids[0], txids[1], txids[2]);
+        let expected = hash_pair(&hash_pair(&a, &b), &hash_pair(&c, &c));
+        assert_eq!(merkle_root(&[a, b, c]), Some(expected));
+
+        // CVE-2012-2459: duplicating the last transaction gives the same root
+        assert_eq!(merkle_root(&[a, b, c]), merkle_root(&[a, b, c, c]));
+    }
+
+    #[test]
+    fn proofs_of_block_100000() {
+        let txids = block_100000_txids();
+        let root = hash_from_hex(BLOCK_100000_ROOT).unwrap();
+
+        for (index, txid) in txids.iter().enumerate() {
+            let proof = merkle_proof(&txids, index).unwrap();
+            assert_eq!(proof.siblings.len(), 2);
+            assert!(verify_proof(txid, &proof, &root));
+
+            // Wrong transaction or wrong position
+            assert!(!verify_proof(&txids[(index + 1) % 4], &proof, &root));
+            let moved = MerkleProof { index: index ^ 1, siblings: proof.siblings.clone() };
+            assert!(!verify_proof(txid, &moved, &root));
+        }
+
+        assert!(merkle_proof(&txids, 4).is_none());
+    }
+
+    #[test]
+    fn proofs_with_odd_levels() {
+        // 5 leaves: odd counts at the first two levels (5, then 3 nodes)
+        let txids: Vec<Hash256> = (0..5u8).map(|i| hash_double_sha256(&[i]).try_into().unwrap()).collect();
+        let root = merkle_root(&txids).unwrap();
+
+        for (index, txid) in txids.iter().enumerate() {
+            let proof = merkle_proof(&txids, index).unwrap();
+            assert_eq!(proof.siblings.len(), 3);
+            assert!(verify_proof(txid, &proof, &root));
+
+            // Index outside the tree
+            let out_of_range = MerkleProof { index: index + 8, siblings: proof.siblings.clone() };
+            assert!(!verify_proof(txid, &out_of_range, &root));
+        }
+    }
+}
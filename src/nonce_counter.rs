@@ -0,0 +1,275 @@
+// ====================================================
+// NONCE-REUSE PROTECTION
+// ====================================================
+// Reusing a nonce with the same key is catastrophic for AES-GCM and
+// ChaCha20-Poly1305: it reveals the XOR of the two plaintexts and lets an
+// attacker forge authentication tags.
+//
+// This stateful encryptor never lets the caller pick a nonce:
+// 1. Each key gets a 64-bit message counter (keys are identified by a
+//    hash, the key itself is never written to disk)
+// 2. The nonce is 4 zero bytes followed by the big-endian counter
+// 3. For each message, under an exclusive lock on "<state file>.lock":
+//    the state file is re-read, the counter incremented and the new state
+//    written and synced to disk. Other encryptors and processes sharing
+//    the state file wait for the lock, so they never get the same counter.
+// 4. The nonce is only used once its reservation is on disk: a crash can
+//    skip nonces but never repeat one
+// 5. Encryption is refused once the per-key message limit is reached
+//
+// State file format: one "<key id> <next counter>" line per key
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::session::AeadSession;
+use crate::{hash_sha256, Cipher};
+
+// Short identifier of a key, safe to store: first 16 bytes of SHA-256(key)
+fn key_id(key: &[u8]) -> String {
+    hex::encode(&hash_sha256(key)[..16])
+}
+
+// Builds the 96-bit nonce for a message counter
+fn counter_nonce(counter: u64) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+// Next counter value of every key, as read from the state file
+struct NonceState {
+    counters: HashMap<String, u64>,
+}
+
+impl NonceState {
+    fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut counters = HashMap::new();
+
+        // A missing file simply means no key has been used yet
+        if path.exists() {
+            for line in fs::read_to_string(path)?.lines() {
+                let mut fields = line.split_whitespace();
+                match (fields.next(), fields.next()) {
+                    (Some(id), Some(counter)) => {
+                        counters.insert(id.to_string(), counter.parse()?);
+                    },
+                    (None, _) => continue,
+                    _ => return Err(format!("malformed nonce state line: {:?}", line).into()),
+                }
+            }
+        }
+
+        Ok(NonceState { counters })
+    }
+
+    // Writes and syncs a temporary file, renames it over the state file,
+    // then syncs the directory so the rename itself is durable
+    fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut ids: Vec<&String> = self.counters.keys().collect();
+        ids.sort();
+        let contents: String = ids
+            .iter()
+            .map(|id| format!("{} {}\n", id, self.counters[*id]))
+            .collect();
+
+        // "<state file>.tmp": with_extension would give keys.a and keys.b the same one
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        let mut tmp_file = File::create(&tmp_path)?;
+        tmp_file.write_all(contents.as_bytes())?;
+        tmp_file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        sync_parent_dir(path)?;
+        Ok(())
+    }
+}
+
+// Directories can only be opened (and synced) as files on Unix
+#[cfg(unix)]
+pub fn sync_parent_dir(path: &Path) -> Result<(), Box<dyn Error>> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn sync_parent_dir(_path: &Path) -> Result<(), Box<dyn Error>> {
+    Ok(())
+}
+
+// Exclusive lock on "<state file>.lock", released when dropped
+struct StateLock(File);
+
+impl StateLock {
+    fn acquire(state_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut lock_path = state_path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(PathBuf::from(lock_path))?;
+        file.lock()?; // Blocks until no other encryptor holds it
+        Ok(StateLock(file))
+    }
+}
+
+impl Drop for StateLock {
+    fn drop(&mut self) {
+        let _ = self.0.unlock();
+    }
+}
+
+// Encryptor bound to one key that picks (and records) nonces itself
+pub struct StatefulEncryptor {
+    cipher: Cipher,
+    session: AeadSession,
+    key_id: String,
+    state_path: PathBuf,
+}
+
+impl StatefulEncryptor {
+    pub fn new(cipher: Cipher, key: &[u8], state_path: &Path) -> Result<Self, Box<dyn Error>> {
+        // Fail early on an unreadable or malformed state file
+        NonceState::load(state_path)?;
+        Ok(StatefulEncryptor {
+            cipher,
+            session: AeadSession::new(cipher, key)?,
+            key_id: key_id(key),
+            state_path: state_path.to_path_buf(),
+        })
+    }
+
+    // Number of messages already encrypted under this key (across runs and processes)
+    pub fn messages_encrypted(&self) -> Result<u64, Box<dyn Error>> {
+        let _lock = StateLock::acquire(&self.state_path)?;
+        Ok(NonceState::load(&self.state_path)?.counters.get(&self.key_id).copied().unwrap_or(0))
+    }
+
+    // Reserves the next counter of this key on disk
+    fn reserve_counter(&self) -> Result<u64, Box<dyn Error>> {
+        let _lock = StateLock::acquire(&self.state_path)?;
+        let mut state = NonceState::load(&self.state_path)?;
+        let counter = state.counters.get(&self.key_id).copied().unwrap_or(0);
+        if counter >= self.cipher.message_limit() {
+            return Err(format!("{} message limit reached for this key, rotate the key", self.cipher.name()).into());
+        }
+
+        state.counters.insert(self.key_id.clone(), counter + 1);
+        state.save(&self.state_path)?;
+        Ok(counter)
+    }
+
+    // Encrypts with the next unused nonce and returns (nonce, ciphertext)
+    pub fn encrypt(&self, message: &[u8]) -> Result<([u8; 12], Vec<u8>), Box<dyn Error>> {
+        let nonce = counter_nonce(self.reserve_counter()?);
+        Ok((nonce, self.session.encrypt(message, &nonce)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    const KEY: [u8; 32] = [9u8; 32];
+
+    fn state_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("rust-cryptocraphy-nonces-{}-{}.txt", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn remove_state(path: &Path) {
+        fs::remove_file(path).unwrap();
+        let _ = fs::remove_file(format!("{}.lock", path.display()));
+    }
+
+    #[test]
+    fn counters_survive_across_instances() {
+        let path = state_path("persist");
+        let encryptor = StatefulEncryptor::new(Cipher::AesGcm, &KEY, &path).unwrap();
+        assert_eq!(encryptor.encrypt(b"a").unwrap().0, counter_nonce(0));
+        assert_eq!(encryptor.encrypt(b"b").unwrap().0, counter_nonce(1));
+        drop(encryptor);
+
+        let encryptor = StatefulEncryptor::new(Cipher::AesGcm, &KEY, &path).unwrap();
+        assert_eq!(encryptor.messages_encrypted().unwrap(), 2);
+        let (nonce, ciphertext) = encryptor.encrypt(b"c").unwrap();
+        assert_eq!(nonce, counter_nonce(2));
+        assert_eq!(Cipher::AesGcm.decrypt(&ciphertext, &KEY, &nonce).unwrap(), b"c");
+
+        // Another key has its own counter
+        let other = StatefulEncryptor::new(Cipher::AesGcm, &[8u8; 32], &path).unwrap();
+        assert_eq!(other.encrypt(b"d").unwrap().0, counter_nonce(0));
+        assert_eq!(encryptor.messages_encrypted().unwrap(), 3);
+        remove_state(&path);
+    }
+
+    #[test]
+    fn shared_state_file_gives_distinct_nonces() {
+        let path = state_path("shared");
+        let nonces: Vec<[u8; 12]> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..4)
+                .map(|_| {
+                    let path = &path;
+                    scope.spawn(move || {
+                        let encryptor = StatefulEncryptor::new(Cipher::ChaCha20Poly1305, &KEY, path).unwrap();
+                        (0..25).map(|_| encryptor.encrypt(b"message").unwrap().0).collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+        });
+
+        assert_eq!(nonces.iter().collect::<HashSet<_>>().len(), 100);
+        let encryptor = StatefulEncryptor::new(Cipher::ChaCha20Poly1305, &KEY, &path).unwrap();
+        assert_eq!(encryptor.messages_encrypted().unwrap(), 100);
+        remove_state(&path);
+    }
+
+    #[test]
+    fn message_limit_is_enforced() {
+        let path = state_path("limit");
+        fs::write(&path, format!("{} {}\n", key_id(&KEY), Cipher::AesGcm.message_limit() - 1)).unwrap();
+        let encryptor = StatefulEncryptor::new(Cipher::AesGcm, &KEY, &path).unwrap();
+        assert!(encryptor.encrypt(b"last").is_ok());
+        assert!(encryptor.encrypt(b"one too many").is_err());
+        assert_eq!(encryptor.messages_encrypted().unwrap(), Cipher::AesGcm.message_limit());
+
+        fs::write(&path, format!("{} {}\n", key_id(&KEY), u64::MAX)).unwrap();
+        let encryptor = StatefulEncryptor::new(Cipher::ChaCha20Poly1305, &KEY, &path).unwrap();
+        assert!(encryptor.encrypt(b"counter exhausted").is_err());
+        remove_state(&path);
+    }
+
+    #[test]
+    fn state_files_differing_by_extension_dont_share_a_temporary_file() {
+        let dir = std::env::temp_dir().join(format!("rust-cryptocraphy-nonces-ext-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (path_a, path_b) = (dir.join("keys.a"), dir.join("keys.b"));
+
+        std::thread::scope(|scope| {
+            for path in [&path_a, &path_b] {
+                scope.spawn(move || {
+                    let encryptor = StatefulEncryptor::new(Cipher::AesGcm, &KEY, path).unwrap();
+                    for _ in 0..50 {
+                        encryptor.encrypt(b"message").unwrap();
+                    }
+                });
+            }
+        });
+
+        for path in [&path_a, &path_b] {
+            let encryptor = StatefulEncryptor::new(Cipher::AesGcm, &KEY, path).unwrap();
+            assert_eq!(encryptor.messages_encrypted().unwrap(), 50);
+        }
+        assert!(!dir.join("keys.tmp").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,200 @@
+// ====================================================
+// PASSWORD HASHING: Argon2id, scrypt and bcrypt
+// ====================================================
+// hash_sha256 and the other hashes of this tool are designed to be fast:
+// an attacker who steals a password database can try billions of guesses
+// per second on a GPU. Password hashes are deliberately slow and
+// memory-hard, and salted so that identical passwords get different hashes.
+//
+// Stored formats (everything needed to verify is in the string):
+// - Argon2id (PHC string): $argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>
+// - scrypt (PHC string):   $scrypt$ln=17,r=8,p=1$<salt>$<hash>
+// - bcrypt (modular crypt): $2b$12$<22 chars salt><31 chars hash>
+//
+// Costs go up as hardware gets faster. A hash made with older (or other)
+// parameters still verifies, and needs_rehash tells when to replace it:
+// right after a successful login, when the password is known.
+
+use std::error::Error;
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Argon2, Version};
+use rand::RngCore;
+use scrypt::Scrypt;
+
+const SALT_LEN: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PasswordAlgorithm {
+    Argon2id,
+    Scrypt,
+    Bcrypt,
+}
+
+impl PasswordAlgorithm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "argon2id" => Some(PasswordAlgorithm::Argon2id),
+            "scrypt" => Some(PasswordAlgorithm::Scrypt),
+            "bcrypt" => Some(PasswordAlgorithm::Bcrypt),
+            _ => None,
+        }
+    }
+
+    // Algorithm of a stored hash, from its prefix
+    fn of_hash(hash: &str) -> Result<Self, Box<dyn Error>> {
+        if hash.starts_with("$argon2") {
+            Ok(PasswordAlgorithm::Argon2id) // Argon2i and Argon2d hashes too, they verify the same way
+        } else if hash.starts_with("$scrypt$") {
+            Ok(PasswordAlgorithm::Scrypt)
+        } else if ["$2a$", "$2b$", "$2x$", "$2y$"].iter().any(|prefix| hash.starts_with(prefix)) {
+            Ok(PasswordAlgorithm::Bcrypt)
+        } else {
+            Err("unrecognized password hash format".into())
+        }
+    }
+}
+
+// Algorithm and costs used for new hashes
+pub struct PasswordPolicy {
+    pub algorithm: PasswordAlgorithm,
+    pub argon2: argon2::Params,
+    pub scrypt: scrypt::Params,
+    pub bcrypt_cost: u32,
+}
+
+// OWASP recommendations: Argon2id with 19 MiB, 2 passes; scrypt N = 2^17, r = 8; bcrypt cost 12
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            algorithm: PasswordAlgorithm::Argon2id,
+            argon2: argon2::Params::default(),
+            scrypt: scrypt::Params::recommended(),
+            bcrypt_cost: bcrypt::DEFAULT_COST,
+        }
+    }
+}
+
+fn random_salt() -> Result<SaltString, Box<dyn Error>> {
+    let mut salt = [0u8; SALT_LEN];
+    rand::rng().fill_bytes(&mut salt);
+    Ok(SaltString::encode_b64(&salt)?)
+}
+
+fn argon2id(params: argon2::Params) -> Argon2<'static> {
+    Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params)
+}
+
+// Hashes a password with a random salt, returning the string to store
+pub fn hash_password(password: &[u8], policy: &PasswordPolicy) -> Result<String, Box<dyn Error>> {
+    let hash = match policy.algorithm {
+        PasswordAlgorithm::Argon2id => argon2id(policy.argon2.clone()).hash_password(password, &random_salt()?)?.to_string(),
+        PasswordAlgorithm::Scrypt => Scrypt.hash_password_customized(password, None, None, policy.scrypt, &random_salt()?)?.to_string(),
+        // bcrypt only uses the first 72 bytes: longer passwords are refused instead of truncated
+        PasswordAlgorithm::Bcrypt => bcrypt::non_truncating_hash(password, policy.bcrypt_cost)?,
+    };
+    Ok(hash)
+}
+
+// Checks a password against a stored hash (constant time comparison).
+// Ok(false) for a wrong password, Err for a malformed hash.
+pub fn verify_password(password: &[u8], hash: &str) -> Result<bool, Box<dyn Error>> {
+    let result = match PasswordAlgorithm::of_hash(hash)? {
+        PasswordAlgorithm::Bcrypt => return Ok(bcrypt::non_truncating_verify(password, hash)?),
+        PasswordAlgorithm::Scrypt => Scrypt.verify_password(password, &PasswordHash::new(hash)?),
+        // The variant and parameters come from the hash
+        PasswordAlgorithm::Argon2id => Argon2::default().verify_password(password, &PasswordHash::new(hash)?),
+    };
+    match result {
+        Ok(()) => Ok(true),
+        Err(argon2::password_hash::Error::Password) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Whether a stored hash was made with another algorithm or other costs than the policy
+pub fn needs_rehash(hash: &str, policy: &PasswordPolicy) -> Result<bool, Box<dyn Error>> {
+    let algorithm = PasswordAlgorithm::of_hash(hash)?;
+    if algorithm != policy.algorithm {
+        return Ok(true);
+    }
+
+    let outdated = match algorithm {
+        PasswordAlgorithm::Argon2id => {
+            let parsed = PasswordHash::new(hash)?;
+            let params = argon2::Params::try_from(&parsed)?;
+            parsed.algorithm != argon2::Algorithm::Argon2id.ident()
+                || parsed.version != Some(Version::V0x13.into())
+                || params.m_cost() != policy.argon2.m_cost()
+                || params.t_cost() != policy.argon2.t_cost()
+                || params.p_cost() != policy.argon2.p_cost()
+        },
+        PasswordAlgorithm::Scrypt => {
+            let params = scrypt::Params::try_from(&PasswordHash::new(hash)?)?;
+            params.log_n() != policy.scrypt.log_n() || params.r() != policy.scrypt.r() || params.p() != policy.scrypt.p()
+        },
+        PasswordAlgorithm::Bcrypt => {
+            let cost = hash.split('$').nth(2).ok_or("malformed bcrypt hash")?;
+            cost.parse::<u32>()? != policy.bcrypt_cost
+        },
+    };
+    Ok(outdated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reference hashes from the argon2 (RFC 9106 parameters), bcrypt and Python hashlib test suites
+    const ARGON2ID_HASH: &str = "$argon2id$v=19$m=256,t=2,p=1$c29tZXNhbHQ$nf65EOgLrQMR/uIPnA4rEsF5h7TKyQwu9U1bMCHGi/4";
+    const SCRYPT_HASH: &str = "$scrypt$ln=4,r=8,p=1$c2FsdHNhbHQ$xdm4IMyPApeWQ+5AiPVw2L3OCnA4OBnnwWGIV2OM5+o";
+    const BCRYPT_HASH: &str = "$2b$04$EGdrhbKUv8Oc9vGiXX0HQOxSg445d458Muh7DAHskb6QbtCvdxcie";
+
+    // Minimal costs, to keep the tests fast
+    fn cheap_policy(algorithm: PasswordAlgorithm) -> PasswordPolicy {
+        PasswordPolicy {
+            algorithm,
+            argon2: argon2::Params::new(256, 2, 1, None).unwrap(),
+            scrypt: scrypt::Params::new(4, 8, 1, 32).unwrap(),
+            bcrypt_cost: 4,
+        }
+    }
+
+    #[test]
+    fn reference_hashes() {
+        assert!(verify_password(b"password", ARGON2ID_HASH).unwrap());
+        assert!(!verify_password(b"Password", ARGON2ID_HASH).unwrap());
+        assert!(verify_password(b"password", SCRYPT_HASH).unwrap());
+        assert!(!verify_password(b"passwore", SCRYPT_HASH).unwrap());
+        assert!(verify_password(b"correctbatteryhorsestapler", BCRYPT_HASH).unwrap());
+        assert!(!verify_password(b"correcthorsebatterystaple", BCRYPT_HASH).unwrap());
+
+        assert!(verify_password(b"password", "$md5$abc").is_err());
+        assert!(verify_password(b"password", "$argon2id$v=19$m=256,t=two,p=1$c29tZXNhbHQ$nf65EOgLrQMR").is_err());
+    }
+
+    #[test]
+    fn hash_verify_and_rehash() {
+        for algorithm in [PasswordAlgorithm::Argon2id, PasswordAlgorithm::Scrypt, PasswordAlgorithm::Bcrypt] {
+            let policy = cheap_policy(algorithm);
+            let hash = hash_password(b"hunter2", &policy).unwrap();
+            assert!(verify_password(b"hunter2", &hash).unwrap(), "{}", hash);
+            assert!(!verify_password(b"hunter3", &hash).unwrap(), "{}", hash);
+
+            // Random salts: the same password never gives the same string
+            assert_ne!(hash_password(b"hunter2", &policy).unwrap(), hash);
+            assert!(!needs_rehash(&hash, &policy).unwrap());
+        }
+
+        let policy = cheap_policy(PasswordAlgorithm::Argon2id);
+        assert!(!needs_rehash(ARGON2ID_HASH, &policy).unwrap());
+        let stronger = PasswordPolicy { argon2: argon2::Params::new(512, 2, 1, None).unwrap(), ..cheap_policy(PasswordAlgorithm::Argon2id) };
+        assert!(needs_rehash(ARGON2ID_HASH, &stronger).unwrap());
+        assert!(needs_rehash(SCRYPT_HASH, &policy).unwrap()); // Other algorithm
+        assert!(needs_rehash(BCRYPT_HASH, &PasswordPolicy { bcrypt_cost: 5, ..cheap_policy(PasswordAlgorithm::Bcrypt) }).unwrap());
+        assert!(needs_rehash(SCRYPT_HASH, &PasswordPolicy { scrypt: scrypt::Params::new(5, 8, 1, 32).unwrap(), ..cheap_policy(PasswordAlgorithm::Scrypt) }).unwrap());
+
+        // bcrypt ignores everything after 72 bytes: refuse rather than truncate
+        assert!(hash_password(&[b'a'; 73], &cheap_policy(PasswordAlgorithm::Bcrypt)).is_err());
+    }
+}
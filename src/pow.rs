@@ -0,0 +1,199 @@
+// ====================================================
+// PROOF OF WORK (Bitcoin mining)
+// ====================================================
+// A block is valid only if the double SHA-256 of its 80-byte header,
+// read as a 256-bit number, is below a target. Since the hash can't be
+// predicted, the only way to find such a header is to try nonces one
+// after another: on average 2^256 / (target + 1) attempts.
+//
+// How it works:
+// 1. The target is stored in the header in compact "nBits" form:
+//    1 byte exponent, 3 bytes mantissa, target = mantissa * 256^(exponent - 3)
+//    (e.g. 0x1d00ffff, the easiest target of the main network)
+// 2. The miner changes the 32-bit nonce (last 4 bytes of the header) and,
+//    once all 2^32 nonces have been tried, the timestamp
+// 3. Threads share the search space: thread i tries attempts i, i + n, ...
+//
+// Byte order: the hash is compared as a little-endian number, which is
+// why valid block hashes are displayed (reversed) with leading zeros.
+
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+
+use sha2::{Digest, Sha256};
+
+pub const HEADER_LEN: usize = 80;
+const TIME_OFFSET: usize = 68;
+const BITS_OFFSET: usize = 72;
+const NONCE_OFFSET: usize = 76;
+
+// SHA-256(SHA-256(header)) on the stack: the mining loop allocates nothing
+fn double_sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(Sha256::digest(data)).into()
+}
+
+// Expands compact nBits into a 256-bit big-endian target
+pub fn target_from_bits(bits: u32) -> Result<[u8; 32], Box<dyn Error>> {
+    let exponent = (bits >> 24) as usize;
+    let mantissa = bits & 0x007f_ffff;
+    if bits & 0x0080_0000 != 0 && mantissa != 0 {
+        return Err(format!("negative target in nBits {:08x}", bits).into());
+    }
+
+    let mut target = [0u8; 32];
+    if exponent <= 3 {
+        let value = mantissa >> (8 * (3 - exponent));
+        target[29..].copy_from_slice(&value.to_be_bytes()[1..]);
+    } else {
+        // The mantissa's 3 bytes end `exponent - 3` bytes before the last byte
+        let mantissa_bytes = &mantissa.to_be_bytes()[1..];
+        for (i, byte) in mantissa_bytes.iter().enumerate() {
+            let position = (32 + i).checked_sub(exponent).filter(|&position| position < 32);
+            match position {
+                Some(position) => target[position] = *byte,
+                None if *byte != 0 => return Err(format!("target of nBits {:08x} overflows 256 bits", bits).into()),
+                None => {},
+            }
+        }
+    }
+    Ok(target)
+}
+
+// Average number of hashes needed to get below the target: 2^256 / (target + 1)
+pub fn expected_attempts(target: &[u8; 32]) -> f64 {
+    let target = target.iter().fold(0.0, |value, &byte| value * 256.0 + byte as f64);
+    2f64.powi(256) / (target + 1.0)
+}
+
+// Whether a block hash (internal byte order) meets the target
+pub fn meets_target(hash: &[u8], target: &[u8; 32]) -> bool {
+    hash.iter().rev().cmp(target.iter()) != std::cmp::Ordering::Greater
+}
+
+pub struct MiningResult {
+    pub header: [u8; HEADER_LEN],
+    pub attempts: u64,
+}
+
+// Searches nonces (then timestamps) of the header until its double SHA-256
+// meets the target of its nBits field. Attempt k uses nonce k mod 2^32 and
+// timestamp + k / 2^32.
+pub fn mine(header: &[u8; HEADER_LEN], threads: usize) -> Result<MiningResult, Box<dyn Error>> {
+    let bits = u32::from_le_bytes(header[BITS_OFFSET..NONCE_OFFSET].try_into().expect("4 bytes"));
+    let target = target_from_bits(bits)?;
+    if target == [0u8; 32] {
+        return Err(format!("nBits {:08x} gives a zero target, no hash can meet it", bits).into());
+    }
+    let base_time = u32::from_le_bytes(header[TIME_OFFSET..BITS_OFFSET].try_into().expect("4 bytes"));
+
+    let found = AtomicBool::new(false);
+    let attempts = AtomicU64::new(0);
+    let threads = threads.max(1) as u64;
+
+    let result = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|first| {
+                let (found, attempts, target) = (&found, &attempts, &target);
+                scope.spawn(move || {
+                    let mut header = *header;
+                    let mut attempt = first;
+                    let mut local_attempts = 0;
+                    while !found.load(Ordering::Relaxed) {
+                        let time = base_time.wrapping_add((attempt >> 32) as u32);
+                        header[TIME_OFFSET..BITS_OFFSET].copy_from_slice(&time.to_le_bytes());
+                        header[NONCE_OFFSET..].copy_from_slice(&(attempt as u32).to_le_bytes());
+
+                        let hash = double_sha256(&header);
+                        local_attempts += 1;
+                        if meets_target(&hash, target) {
+                            found.store(true, Ordering::Relaxed);
+                            attempts.fetch_add(local_attempts, Ordering::Relaxed);
+                            return Some(header);
+                        }
+                        attempt += threads;
+                    }
+                    attempts.fetch_add(local_attempts, Ordering::Relaxed);
+                    None
+                })
+            })
+            .collect();
+
+        // Several threads may succeed at the same time: keep the first one
+        workers.into_iter().filter_map(|worker| worker.join().expect("mining thread panicked")).next()
+    });
+
+    let header = result.expect("a thread always finds a header before stopping");
+    Ok(MiningResult { header, attempts: attempts.load(Ordering::Relaxed) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Genesis block header, hash 000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f
+    const GENESIS_HEADER: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+
+    #[test]
+    fn bits_decoding() {
+        assert_eq!(hex::encode(target_from_bits(0x1d00ffff).unwrap()), "00000000ffff0000000000000000000000000000000000000000000000000000");
+        // Block 100000
+        assert_eq!(hex::encode(target_from_bits(0x1b04864c).unwrap()), "000000000004864c000000000000000000000000000000000000000000000000");
+        // Regtest
+        assert_eq!(hex::encode(target_from_bits(0x207fffff).unwrap()), "7fffff0000000000000000000000000000000000000000000000000000000000");
+        // Small exponents shift the mantissa right
+        assert_eq!(hex::encode(&target_from_bits(0x03123456).unwrap()[29..]), "123456");
+        assert_eq!(hex::encode(&target_from_bits(0x02123456).unwrap()[29..]), "001234");
+        assert_eq!(target_from_bits(0x01003456).unwrap(), [0u8; 32]);
+
+        assert_eq!(expected_attempts(&target_from_bits(0x1d00ffff).unwrap()).round(), 4295032833.0);
+    }
+
+    #[test]
+    fn negative_and_overflowing_bits() {
+        // Sign bit set with a non-zero mantissa
+        assert!(target_from_bits(0x04923456).is_err());
+        assert!(target_from_bits(0x1d80ffff).is_err());
+        // A zero mantissa with the sign bit is zero, not negative
+        assert_eq!(target_from_bits(0x1d800000).unwrap(), [0u8; 32]);
+        // Mantissa bytes past 256 bits
+        assert!(target_from_bits(0x21010000).is_err());
+        assert!(target_from_bits(0xff123456).is_err());
+        // ... unless they are zero
+        assert_eq!(target_from_bits(0x2100ffff).unwrap()[..2], [0xff, 0xff]);
+    }
+
+    #[test]
+    fn genesis_block_meets_its_target() {
+        let header = hex::decode(GENESIS_HEADER).unwrap();
+        let hash = double_sha256(&header);
+        let mut displayed = hash;
+        displayed.reverse();
+        assert_eq!(hex::encode(displayed), "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f");
+
+        let target = target_from_bits(0x1d00ffff).unwrap();
+        assert!(meets_target(&hash, &target));
+        // 0x1b04864c is about 16000 times harder
+        assert!(!meets_target(&hash, &target_from_bits(0x1b04864c).unwrap()));
+    }
+
+    #[test]
+    fn low_difficulty_mining() {
+        // Genesis header with nBits 0x2000ffff: about 256 attempts
+        let mut header: [u8; HEADER_LEN] = hex::decode(GENESIS_HEADER).unwrap().try_into().unwrap();
+        header[BITS_OFFSET..NONCE_OFFSET].copy_from_slice(&0x2000ffffu32.to_le_bytes());
+        header[NONCE_OFFSET..].copy_from_slice(&[0; 4]);
+
+        for threads in [1, 4] {
+            let result = mine(&header, threads).unwrap();
+            assert!(meets_target(&double_sha256(&result.header), &target_from_bits(0x2000ffff).unwrap()));
+            assert!(result.attempts >= 1);
+            // Only the nonce changed
+            assert_eq!(result.header[..NONCE_OFFSET], header[..NONCE_OFFSET]);
+        }
+
+        // A zero target can never be met
+        header[BITS_OFFSET..NONCE_OFFSET].copy_from_slice(&0x01003456u32.to_le_bytes());
+        assert!(mine(&header, 1).is_err());
+    }
+}
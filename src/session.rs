@@ -0,0 +1,182 @@
+// ====================================================
+// REUSABLE CIPHER SESSIONS
+// ====================================================
+// encrypt_aes_gcm and encrypt_chacha20poly1305 build a new cipher on every
+// call. For AES this means redoing the key expansion (14 round keys for
+// AES-256) and the GHASH key setup for each message, which dominates the
+// cost when messages are small (RPC calls, network packets, ...).
+//
+// A session is created once from a key and then encrypts and decrypts any
+// number of messages with the already-expanded key.
+
+use std::error::Error;
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::aes::Aes256;
+use aes_gcm::{AesGcm, Nonce};
+use chacha20poly1305::ChaCha20Poly1305;
+use chacha20poly1305::Nonce as ChaChaNonce;
+use generic_array::typenum::U12;
+
+use crate::Cipher;
+
+// 96-bit nonces for both AEADs
+const NONCE_LEN: usize = 12;
+
+// A cipher with its key schedule already computed
+pub enum AeadSession {
+    AesGcm(Box<AesGcm<Aes256, U12>>),
+    ChaCha20Poly1305(Box<ChaCha20Poly1305>),
+}
+
+impl AeadSession {
+    // Expands the 256-bit key once
+    pub fn new(cipher: Cipher, key: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let session = match cipher {
+            Cipher::AesGcm => AeadSession::AesGcm(Box::new(AesGcm::new_from_slice(key)?)),
+            Cipher::ChaCha20Poly1305 => AeadSession::ChaCha20Poly1305(Box::new(ChaCha20Poly1305::new_from_slice(key)?)),
+        };
+        Ok(session)
+    }
+
+    pub fn encrypt(&self, message: &[u8], nonce: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.encrypt_with_aad(message, &[], nonce)
+    }
+
+    pub fn decrypt(&self, ciphertext: &[u8], nonce: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.decrypt_with_aad(ciphertext, &[], nonce)
+    }
+
+    // Associated data is authenticated by the tag but not encrypted (headers, record numbers, ...)
+    pub fn encrypt_with_aad(&self, message: &[u8], aad: &[u8], nonce: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        check_nonce(nonce)?;
+        let payload = Payload { msg: message, aad };
+        let ciphertext = match self {
+            AeadSession::AesGcm(cipher) => cipher.encrypt(Nonce::<U12>::from_slice(nonce), payload),
+            AeadSession::ChaCha20Poly1305(cipher) => cipher.encrypt(ChaChaNonce::from_slice(nonce), payload),
+        };
+        ciphertext.map_err(|_| "encryption failure".into())
+    }
+
+    pub fn decrypt_with_aad(&self, ciphertext: &[u8], aad: &[u8], nonce: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        check_nonce(nonce)?;
+        let payload = Payload { msg: ciphertext, aad };
+        let plaintext = match self {
+            AeadSession::AesGcm(cipher) => cipher.decrypt(Nonce::<U12>::from_slice(nonce), payload),
+            AeadSession::ChaCha20Poly1305(cipher) => cipher.decrypt(ChaChaNonce::from_slice(nonce), payload),
+        };
+        plaintext.map_err(|_| "authentication tag mismatch".into())
+    }
+}
+
+fn check_nonce(nonce: &[u8]) -> Result<(), Box<dyn Error>> {
+    if nonce.len() != NONCE_LEN {
+        return Err(format!("nonce must be {} bytes, got {}", NONCE_LEN, nonce.len()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [5u8; 32];
+    const NONCE: [u8; 12] = [6u8; 12];
+
+    #[test]
+    fn matches_one_shot_functions() {
+        for cipher in [Cipher::AesGcm, Cipher::ChaCha20Poly1305] {
+            let session = AeadSession::new(cipher, &KEY).unwrap();
+            for len in [0, 1, 16, 100, 1000] {
+                let message = vec![0xabu8; len];
+                let ciphertext = session.encrypt(&message, &NONCE).unwrap();
+                assert_eq!(ciphertext, cipher.encrypt(&message, &KEY, &NONCE));
+                assert_eq!(session.decrypt(&ciphertext, &NONCE).unwrap(), message);
+                assert_eq!(cipher.decrypt(&ciphertext, &KEY, &NONCE).unwrap(), message);
+            }
+
+            // The same session keeps working across messages and nonces
+            let other_nonce = [7u8; 12];
+            let ciphertext = session.encrypt(b"second", &other_nonce).unwrap();
+            assert_eq!(ciphertext, cipher.encrypt(b"second", &KEY, &other_nonce));
+            assert!(session.decrypt(&ciphertext, &NONCE).is_err());
+        }
+    }
+
+    #[test]
+    fn wrong_sizes_are_rejected() {
+        for cipher in [Cipher::AesGcm, Cipher::ChaCha20Poly1305] {
+            assert!(AeadSession::new(cipher, &KEY[..16]).is_err());
+            let session = AeadSession::new(cipher, &KEY).unwrap();
+            let ciphertext = session.encrypt(b"message", &NONCE).unwrap();
+            for nonce_len in [0, 8, 11, 13, 24] {
+                let nonce = vec![6u8; nonce_len];
+                assert!(session.encrypt(b"message", &nonce).is_err());
+                assert!(session.decrypt(&ciphertext, &nonce).is_err());
+            }
+        }
+    }
+
+    struct AadVector {
+        cipher: Cipher,
+        key: Vec<u8>,
+        nonce: &'static str,
+        aad: &'static str,
+        plaintext: Vec<u8>,
+        ciphertext: &'static str, // Followed by the tag
+    }
+
+    fn aad_vectors() -> [AadVector; 2] {
+        [
+            // RFC 8439 section 2.8.2
+            AadVector {
+                cipher: Cipher::ChaCha20Poly1305,
+                key: (0x80..0xa0).collect(),
+                nonce: "070000004041424344454647",
+                aad: "50515253c0c1c2c3c4c5c6c7",
+                plaintext: b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.".to_vec(),
+                ciphertext: "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d63dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b3692ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc3ff4def08e4b7a9de576d26586cec64b61161ae10b594f09e26a7e902ecbd0600691",
+            },
+            // GCM specification (McGrew and Viega), test case 16
+            AadVector {
+                cipher: Cipher::AesGcm,
+                key: hex::decode("feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308").unwrap(),
+                nonce: "cafebabefacedbaddecaf888",
+                aad: "feedfacedeadbeeffeedfacedeadbeefabaddad2",
+                plaintext: hex::decode("d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39").unwrap(),
+                ciphertext: "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f66276fc6ece0f4e1768cddf8853bb2d551b",
+            },
+        ]
+    }
+
+    #[test]
+    fn aad_vectors_and_tampering() {
+        for vector in aad_vectors() {
+            let session = AeadSession::new(vector.cipher, &vector.key).unwrap();
+            let (nonce, aad) = (hex::decode(vector.nonce).unwrap(), hex::decode(vector.aad).unwrap());
+
+            let ciphertext = session.encrypt_with_aad(&vector.plaintext, &aad, &nonce).unwrap();
+            assert_eq!(hex::encode(&ciphertext), vector.ciphertext, "{}", vector.cipher.name());
+            assert_eq!(session.decrypt_with_aad(&ciphertext, &aad, &nonce).unwrap(), vector.plaintext);
+
+            // The associated data is authenticated: any change is rejected
+            let mut other_aad = aad.clone();
+            other_aad[0] ^= 1;
+            assert!(session.decrypt_with_aad(&ciphertext, &other_aad, &nonce).is_err());
+            assert!(session.decrypt_with_aad(&ciphertext, &aad[1..], &nonce).is_err());
+            assert!(session.decrypt(&ciphertext, &nonce).is_err());
+            assert!(session.decrypt_with_aad(&ciphertext, &aad, &[0u8; 8]).is_err());
+        }
+    }
+
+    #[test]
+    fn empty_aad_is_plain_encryption() {
+        for cipher in [Cipher::AesGcm, Cipher::ChaCha20Poly1305] {
+            let session = AeadSession::new(cipher, &KEY).unwrap();
+            let ciphertext = session.encrypt_with_aad(b"message", &[], &NONCE).unwrap();
+            assert_eq!(ciphertext, session.encrypt(b"message", &NONCE).unwrap());
+            assert_eq!(session.decrypt(&ciphertext, &NONCE).unwrap(), b"message");
+            assert!(session.decrypt_with_aad(&ciphertext, b"aad", &NONCE).is_err());
+        }
+    }
+}
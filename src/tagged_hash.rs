@@ -0,0 +1,201 @@
+// ====================================================
+// TAGGED HASHES (BIP-340)
+// ====================================================
+// Taproot and Schnorr signatures hash data under a tag, so that a hash
+// computed for one purpose can never be replayed as a hash for another:
+//
+//     tagged_hash(tag, msg) = SHA256(SHA256(tag) || SHA256(tag) || msg)
+//
+// The prefix SHA256(tag) || SHA256(tag) is exactly one 64-byte SHA-256
+// block, so the hash state after it (the "midstate") only depends on the
+// tag. It's computed once per tag, and each hash then starts from it: the
+// prefix costs nothing. The midstates of the common Bitcoin tags are
+// precomputed below, other tags get theirs computed when a hasher is created.
+
+use sha2::digest::generic_array::GenericArray;
+use sha2::{Digest, Sha256};
+
+use crate::hash_registry::StreamingHasher;
+
+const BLOCK_LEN: usize = 64;
+
+// SHA-256 initial hash value (FIPS 180-4, section 5.3.3)
+const SHA256_IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+pub struct KnownTag {
+    pub name: &'static str,
+    midstate: [u32; 8],
+}
+
+// Midstates of the tags used by BIP-340 (Schnorr), BIP-341 and BIP-342 (Taproot)
+pub const KNOWN_TAGS: &[KnownTag] = &[
+    KnownTag {
+        name: "BIP0340/challenge",
+        midstate: [0x9cecba11, 0x23925381, 0x11679112, 0xd1627e0f, 0x97c87550, 0x003cc765, 0x90f61164, 0x33e9b66a],
+    },
+    KnownTag {
+        name: "BIP0340/aux",
+        midstate: [0x24dd3219, 0x4eba7e70, 0xca0fabb9, 0x0fa3166d, 0x3afbe4b1, 0x4c44df97, 0x4aac2739, 0x249e850a],
+    },
+    KnownTag {
+        name: "BIP0340/nonce",
+        midstate: [0x46615b35, 0xf4bfbff7, 0x9f8dc671, 0x83627ab3, 0x60217180, 0x57358661, 0x21a29e54, 0x68b07b4c],
+    },
+    KnownTag {
+        name: "TapLeaf",
+        midstate: [0x9ce0e4e6, 0x7c116c39, 0x38b3caf2, 0xc30f5089, 0xd3f3936c, 0x47636e60, 0x7db33eea, 0xddc6f0c9],
+    },
+    KnownTag {
+        name: "TapBranch",
+        midstate: [0x23a865a9, 0xb8a40da7, 0x977c1e04, 0xc49e246f, 0xb5be1376, 0x9d24c9b7, 0xb583b5d4, 0xa8d226d2],
+    },
+    KnownTag {
+        name: "TapTweak",
+        midstate: [0xd129a2f3, 0x701c655d, 0x6583b6c3, 0xb9419727, 0x95f4e232, 0x94fd54f4, 0xa2ae8d85, 0x47ca590b],
+    },
+    KnownTag {
+        name: "TapSighash",
+        midstate: [0xf504a425, 0xd7f8783b, 0x1363868a, 0xe3e55658, 0x6eee945d, 0xbc7888dd, 0x02a6e2c3, 0x1873fe9f],
+    },
+];
+
+// SHA-256 state after the block SHA256(tag) || SHA256(tag)
+fn compute_midstate(tag: &str) -> [u32; 8] {
+    let tag_hash = Sha256::digest(tag.as_bytes());
+    let mut block = [0u8; BLOCK_LEN];
+    block[..32].copy_from_slice(&tag_hash);
+    block[32..].copy_from_slice(&tag_hash);
+
+    let mut state = SHA256_IV;
+    sha2::compress256(&mut state, &[GenericArray::from(block)]);
+    state
+}
+
+fn midstate(tag: &str) -> [u32; 8] {
+    match KNOWN_TAGS.iter().find(|known| known.name == tag) {
+        Some(known) => known.midstate,
+        None => compute_midstate(tag),
+    }
+}
+
+// Streaming tagged hash, resumed from the midstate of its tag
+pub struct TaggedHasher {
+    state: [u32; 8],
+    buffer: [u8; BLOCK_LEN],
+    buffered: usize,
+    len: u64, // Bytes hashed, including the 64-byte tag prefix
+}
+
+impl TaggedHasher {
+    pub fn new(tag: &str) -> Self {
+        TaggedHasher { state: midstate(tag), buffer: [0u8; BLOCK_LEN], buffered: 0, len: BLOCK_LEN as u64 }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+
+        // Complete a partially filled block first
+        if self.buffered > 0 {
+            let taken = data.len().min(BLOCK_LEN - self.buffered);
+            self.buffer[self.buffered..self.buffered + taken].copy_from_slice(&data[..taken]);
+            self.buffered += taken;
+            data = &data[taken..];
+            if self.buffered < BLOCK_LEN {
+                return;
+            }
+            sha2::compress256(&mut self.state, &[GenericArray::from(self.buffer)]);
+            self.buffered = 0;
+        }
+
+        let (blocks, rest) = data.as_chunks::<BLOCK_LEN>();
+        let blocks: Vec<_> = blocks.iter().map(|block| GenericArray::from(*block)).collect();
+        sha2::compress256(&mut self.state, &blocks);
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
+    }
+
+    // Standard SHA-256 padding: 0x80, zeros, then the bit length on 8 bytes
+    pub fn finalize(mut self) -> [u8; 32] {
+        let bit_len = self.len * 8;
+        let mut padding = [0u8; 2 * BLOCK_LEN];
+        padding[0] = 0x80;
+        let padding_len = if self.buffered < BLOCK_LEN - 8 { BLOCK_LEN - self.buffered } else { 2 * BLOCK_LEN - self.buffered };
+        padding[padding_len - 8..padding_len].copy_from_slice(&bit_len.to_be_bytes());
+
+        let len = self.len;
+        self.update(&padding[..padding_len]);
+        debug_assert_eq!(self.buffered, 0);
+        self.len = len;
+
+        let mut hash = [0u8; 32];
+        for (bytes, word) in hash.chunks_exact_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        hash
+    }
+}
+
+impl StreamingHasher for TaggedHasher {
+    fn update(&mut self, data: &[u8]) {
+        TaggedHasher::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        TaggedHasher::finalize(*self).to_vec()
+    }
+}
+
+// SHA256(SHA256(tag) || SHA256(tag) || msg)
+pub fn tagged_hash(tag: &str, msg: &[u8]) -> [u8; 32] {
+    let mut hasher = TaggedHasher::new(tag);
+    hasher.update(msg);
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Direct computation from the definition, without midstates
+    fn reference_tagged_hash(tag: &str, msg: &[u8]) -> [u8; 32] {
+        let tag_hash = Sha256::digest(tag.as_bytes());
+        Sha256::new().chain_update(tag_hash).chain_update(tag_hash).chain_update(msg).finalize().into()
+    }
+
+    #[test]
+    fn precomputed_midstates() {
+        for known in KNOWN_TAGS {
+            assert_eq!(known.midstate, compute_midstate(known.name), "{}", known.name);
+        }
+    }
+
+    #[test]
+    fn tagged_hash_vectors() {
+        let long: Vec<u8> = (0..200).collect();
+        assert_eq!(hex::encode(tagged_hash("BIP0340/challenge", b"")), "c216d352f5818b7b4beacd4ae0a26fe888080823d2a598856661bcd54f1b3713");
+        assert_eq!(hex::encode(tagged_hash("TapLeaf", b"abc")), "83a56308a9c56f467e8df293da5ae5fdbc85b871952a83c4bf0575ee948ec230");
+        assert_eq!(hex::encode(tagged_hash("TapTweak", &long)), "bb889ca1c4cdec6e74736b3ea11b34619b3f4c6303d5a4a26ac9303d86dfacc7");
+        // Unknown tag: midstate computed on the fly
+        assert_eq!(hex::encode(tagged_hash("my-protocol/v1", b"abc")), "323f8516b2d696f7209d69af4a799ff77f09f5e0921b22e0eb088f39103d8ae0");
+    }
+
+    #[test]
+    fn streaming_matches_the_definition() {
+        let data: Vec<u8> = (0..300u32).map(|i| (i * 7) as u8).collect();
+        for len in [0, 1, 55, 56, 63, 64, 65, 119, 120, 128, 300] {
+            for tag in ["TapSighash", "BIP0340/nonce", "other"] {
+                let expected = reference_tagged_hash(tag, &data[..len]);
+                assert_eq!(tagged_hash(tag, &data[..len]), expected);
+
+                // Fed in uneven pieces
+                let mut hasher = TaggedHasher::new(tag);
+                for piece in data[..len].chunks(13) {
+                    hasher.update(piece);
+                }
+                assert_eq!(hasher.finalize(), expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,494 @@
+// ====================================================
+// TRANSPARENCY LOG (RFC 6962, Certificate Transparency)
+// ====================================================
+// An append-only log where every entry is committed to by a Merkle tree.
+// Anyone can check that an entry is in the log (inclusion proof) and that a
+// newer version of the log only added entries to an older one (consistency
+// proof): entries can't be silently modified or removed.
+//
+// How it works:
+// 1. Leaf hash = SHA-256(0x00 || entry), node hash = SHA-256(0x01 || left || right).
+//    The different prefixes (domain separation) make it impossible to pass
+//    an inner node off as a leaf, unlike the Bitcoin tree.
+// 2. A tree of n leaves is split at k, the largest power of two below n:
+//    the left subtree is always complete, no node is duplicated
+// 3. The log operator periodically signs a tree head (size, timestamp,
+//    root hash) with Ed25519; clients keep the last head they saw
+// 4. Proofs are the sibling hashes needed to recompute a root
+//
+// Storage: "<dir>/entries" holds every entry as a 4-byte big-endian length
+// followed by the data. Entries are only ever appended to the file, under an
+// exclusive lock on it.
+
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+
+use crate::hash_sha256;
+use crate::nonce_counter::sync_parent_dir;
+
+pub type Hash = [u8; 32];
+
+fn sha256(data: &[u8]) -> Hash {
+    hash_sha256(data).try_into().expect("SHA-256 output is 32 bytes")
+}
+
+fn leaf_hash(entry: &[u8]) -> Hash {
+    let mut data = Vec::with_capacity(entry.len() + 1);
+    data.push(0x00);
+    data.extend_from_slice(entry);
+    sha256(&data)
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut data = [0u8; 65];
+    data[0] = 0x01;
+    data[1..33].copy_from_slice(left);
+    data[33..].copy_from_slice(right);
+    sha256(&data)
+}
+
+// Largest power of two strictly smaller than n (n > 1)
+fn split_point(n: usize) -> usize {
+    1 << (usize::BITS - (n - 1).leading_zeros() - 1)
+}
+
+// Merkle Tree Hash of a list of leaf hashes (RFC 6962 section 2.1)
+fn tree_hash(leaves: &[Hash]) -> Hash {
+    match leaves.len() {
+        0 => sha256(&[]),
+        1 => leaves[0],
+        n => {
+            let k = split_point(n);
+            node_hash(&tree_hash(&leaves[..k]), &tree_hash(&leaves[k..]))
+        },
+    }
+}
+
+// Audit path of leaf m (RFC 6962 section 2.1.1)
+fn inclusion_path(m: usize, leaves: &[Hash]) -> Vec<Hash> {
+    let n = leaves.len();
+    if n <= 1 {
+        return Vec::new();
+    }
+    let k = split_point(n);
+    if m < k {
+        let mut path = inclusion_path(m, &leaves[..k]);
+        path.push(tree_hash(&leaves[k..]));
+        path
+    } else {
+        let mut path = inclusion_path(m - k, &leaves[k..]);
+        path.push(tree_hash(&leaves[..k]));
+        path
+    }
+}
+
+// Consistency proof between the first m leaves and the whole list (RFC 6962 section 2.1.2)
+fn consistency_subproof(m: usize, leaves: &[Hash], complete_subtree: bool) -> Vec<Hash> {
+    let n = leaves.len();
+    if m == n {
+        return if complete_subtree { Vec::new() } else { vec![tree_hash(leaves)] };
+    }
+    let k = split_point(n);
+    if m <= k {
+        let mut proof = consistency_subproof(m, &leaves[..k], complete_subtree);
+        proof.push(tree_hash(&leaves[k..]));
+        proof
+    } else {
+        let mut proof = consistency_subproof(m - k, &leaves[k..], false);
+        proof.push(tree_hash(&leaves[..k]));
+        proof
+    }
+}
+
+// Checks that `leaf` is entry `index` of the tree of `tree_size` leaves with
+// the given root (RFC 9162 section 2.1.3.2)
+pub fn verify_inclusion(leaf: &Hash, index: u64, tree_size: u64, proof: &[Hash], root: &Hash) -> bool {
+    if index >= tree_size {
+        return false;
+    }
+
+    let (mut f_n, mut s_n) = (index, tree_size - 1);
+    let mut r = *leaf;
+    for p in proof {
+        if s_n == 0 {
+            return false;
+        }
+        if f_n & 1 == 1 || f_n == s_n {
+            r = node_hash(p, &r);
+            while f_n & 1 == 0 && f_n != 0 {
+                f_n >>= 1;
+                s_n >>= 1;
+            }
+        } else {
+            r = node_hash(&r, p);
+        }
+        f_n >>= 1;
+        s_n >>= 1;
+    }
+    s_n == 0 && r == *root
+}
+
+// Checks that the tree of `new_size` leaves extends the tree of `old_size`
+// leaves (RFC 9162 section 2.1.4.2)
+pub fn verify_consistency(old_size: u64, new_size: u64, old_root: &Hash, new_root: &Hash, proof: &[Hash]) -> bool {
+    if old_size > new_size {
+        return false;
+    }
+    if old_size == new_size {
+        return proof.is_empty() && old_root == new_root;
+    }
+    // Every tree extends the empty tree
+    if old_size == 0 {
+        return proof.is_empty();
+    }
+    if proof.is_empty() {
+        return false;
+    }
+
+    // When the old tree is a complete subtree, its root is the first node of the path
+    let mut path = Vec::with_capacity(proof.len() + 1);
+    if old_size.is_power_of_two() {
+        path.push(*old_root);
+    }
+    path.extend_from_slice(proof);
+
+    let (mut f_n, mut s_n) = (old_size - 1, new_size - 1);
+    while f_n & 1 == 1 {
+        f_n >>= 1;
+        s_n >>= 1;
+    }
+
+    let (mut f_r, mut s_r) = (path[0], path[0]);
+    for c in &path[1..] {
+        if s_n == 0 {
+            return false;
+        }
+        if f_n & 1 == 1 || f_n == s_n {
+            f_r = node_hash(c, &f_r);
+            s_r = node_hash(c, &s_r);
+            while f_n & 1 == 0 && f_n != 0 {
+                f_n >>= 1;
+                s_n >>= 1;
+            }
+        } else {
+            s_r = node_hash(&s_r, c);
+        }
+        f_n >>= 1;
+        s_n >>= 1;
+    }
+    f_r == *old_root && s_r == *new_root && s_n == 0
+}
+
+// Root of the log at a given size, signed by the log operator
+pub struct SignedTreeHead {
+    pub tree_size: u64,
+    pub timestamp: u64, // Milliseconds since the Unix epoch
+    pub root_hash: Hash,
+    pub signature: [u8; 64],
+}
+
+impl SignedTreeHead {
+    // TreeHeadSignature structure of RFC 6962 section 3.5:
+    // version (v1 = 0), signature type (tree_hash = 1), timestamp, tree size, root hash
+    fn signed_data(tree_size: u64, timestamp: u64, root_hash: &Hash) -> Vec<u8> {
+        let mut data = vec![0, 1];
+        data.extend_from_slice(&timestamp.to_be_bytes());
+        data.extend_from_slice(&tree_size.to_be_bytes());
+        data.extend_from_slice(root_hash);
+        data
+    }
+
+    pub fn verify(&self, verifying_key: &VerifyingKey) -> bool {
+        let data = Self::signed_data(self.tree_size, self.timestamp, &self.root_hash);
+        verifying_key.verify(&data, &Signature::from_bytes(&self.signature)).is_ok()
+    }
+}
+
+// Leaf hashes of the complete records at the start of `data`. Returns the
+// number of bytes they take: a partial record at the end is left out.
+fn parse_records(data: &[u8], leaves: &mut Vec<Hash>) -> usize {
+    let mut rest = data;
+    while let Some((length, tail)) = rest.split_first_chunk::<4>() {
+        let length = u32::from_be_bytes(*length) as usize;
+        if tail.len() < length {
+            break;
+        }
+        leaves.push(leaf_hash(&tail[..length]));
+        rest = &tail[length..];
+    }
+    data.len() - rest.len()
+}
+
+// Append-only log stored in a directory
+pub struct TransparencyLog {
+    entries_path: PathBuf,
+    leaves: Vec<Hash>, // Leaf hashes of all entries, rebuilt when opening
+    length: u64,       // Bytes of "entries" taken by complete records
+}
+
+impl TransparencyLog {
+    // Opens the existing log in `dir`.
+    // An append syncs its record before returning, so a partial record at the
+    // end of the file is one whose append never completed (crash, full disk):
+    // it isn't part of the log and the next append cuts it off.
+    pub fn open(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        if !dir.is_dir() {
+            return Err(format!("{}: no such log directory", dir.display()).into());
+        }
+        let entries_path = dir.join("entries");
+
+        let mut leaves = Vec::new();
+        let mut length = 0;
+        if entries_path.exists() {
+            let data = fs::read(&entries_path)?;
+            length = parse_records(&data, &mut leaves) as u64;
+        }
+
+        Ok(TransparencyLog { entries_path, leaves, length })
+    }
+
+    // Opens the log in `dir`, creating an empty one if needed
+    pub fn open_or_create(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        if !dir.is_dir() {
+            fs::create_dir_all(dir)?;
+            sync_parent_dir(dir)?;
+        }
+        Self::open(dir)
+    }
+
+    pub fn size(&self) -> u64 {
+        self.leaves.len() as u64
+    }
+
+    // Appends an entry (written to disk before returning) and returns its index.
+    // The entries file stays locked for the whole append, so concurrent
+    // appenders each get their own index.
+    pub fn append(&mut self, entry: &[u8]) -> Result<u64, Box<dyn Error>> {
+        let length = u32::try_from(entry.len()).map_err(|_| "log entries are limited to 4 GiB")?;
+
+        let mut record = Vec::with_capacity(entry.len() + 4);
+        record.extend_from_slice(&length.to_be_bytes());
+        record.extend_from_slice(entry);
+
+        let created = !self.entries_path.exists();
+        let mut file = OpenOptions::new().create(true).truncate(false).read(true).write(true).open(&self.entries_path)?;
+        file.lock()?; // Blocks until no other appender holds it
+
+        // Entries appended by other processes since the log was opened
+        let mut appended = Vec::new();
+        file.seek(SeekFrom::Start(self.length))?;
+        file.read_to_end(&mut appended)?;
+        self.length += parse_records(&appended, &mut self.leaves) as u64;
+
+        // Cut off a partial record left by an interrupted append
+        if file.metadata()?.len() > self.length {
+            file.set_len(self.length)?;
+        }
+        file.seek(SeekFrom::Start(self.length))?;
+        file.write_all(&record)?;
+        file.sync_data()?;
+        if created {
+            sync_parent_dir(&self.entries_path)?;
+        }
+        file.unlock()?;
+
+        self.leaves.push(leaf_hash(entry));
+        self.length += record.len() as u64;
+        Ok(self.size() - 1)
+    }
+
+    // Leaf hash of entry `index`
+    pub fn leaf(&self, index: u64) -> Result<Hash, Box<dyn Error>> {
+        self.leaves.get(index as usize).copied().ok_or_else(|| format!("the log only has {} entries", self.size()).into())
+    }
+
+    fn checked_size(&self, tree_size: u64) -> Result<usize, Box<dyn Error>> {
+        if tree_size > self.size() {
+            return Err(format!("the log only has {} entries", self.size()).into());
+        }
+        Ok(tree_size as usize)
+    }
+
+    // Root hash of the first `tree_size` entries
+    pub fn root_hash(&self, tree_size: u64) -> Result<Hash, Box<dyn Error>> {
+        let tree_size = self.checked_size(tree_size)?;
+        Ok(tree_hash(&self.leaves[..tree_size]))
+    }
+
+    // Signs the current root with the log operator's key
+    pub fn sign_tree_head(&self, signing_key: &SigningKey) -> Result<SignedTreeHead, Box<dyn Error>> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+        let root_hash = self.root_hash(self.size())?;
+        let data = SignedTreeHead::signed_data(self.size(), timestamp, &root_hash);
+        Ok(SignedTreeHead {
+            tree_size: self.size(),
+            timestamp,
+            root_hash,
+            signature: signing_key.sign(&data).to_bytes(),
+        })
+    }
+
+    // Proof that entry `index` is in the tree of the first `tree_size` entries
+    pub fn inclusion_proof(&self, index: u64, tree_size: u64) -> Result<Vec<Hash>, Box<dyn Error>> {
+        let tree_size = self.checked_size(tree_size)?;
+        if index >= tree_size as u64 {
+            return Err(format!("entry {} isn't in a tree of {} entries", index, tree_size).into());
+        }
+        Ok(inclusion_path(index as usize, &self.leaves[..tree_size]))
+    }
+
+    // Proof that the tree of `new_size` entries extends the tree of `old_size` entries
+    pub fn consistency_proof(&self, old_size: u64, new_size: u64) -> Result<Vec<Hash>, Box<dyn Error>> {
+        let new_size = self.checked_size(new_size)?;
+        if old_size > new_size as u64 {
+            return Err("the old tree can't be larger than the new one".into());
+        }
+        if old_size == 0 {
+            return Ok(Vec::new());
+        }
+        Ok(consistency_subproof(old_size as usize, &self.leaves[..new_size], true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test tree of the Certificate Transparency reference implementation
+    const LEAVES: [&[u8]; 8] = [
+        b"",
+        b"\x00",
+        b"\x10",
+        b"\x20\x21",
+        b"\x30\x31",
+        b"\x40\x41\x42\x43",
+        b"\x50\x51\x52\x53\x54\x55\x56\x57",
+        b"\x60\x61\x62\x63\x64\x65\x66\x67\x68\x69\x6a\x6b\x6c\x6d\x6e\x6f",
+    ];
+
+    fn test_log() -> (TransparencyLog, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rust-cryptocraphy-log-test-{}-{:?}", std::process::id(), std::thread::current().id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut log = TransparencyLog::open_or_create(&dir).unwrap();
+        for entry in LEAVES {
+            log.append(entry).unwrap();
+        }
+        (log, dir)
+    }
+
+    #[test]
+    fn reference_root_hashes() {
+        let (log, dir) = test_log();
+        assert_eq!(hex::encode(log.root_hash(0).unwrap()), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(hex::encode(log.root_hash(1).unwrap()), "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d");
+        assert_eq!(hex::encode(log.root_hash(8).unwrap()), "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328");
+
+        // Reopening the log rebuilds the same tree from disk
+        let reopened = TransparencyLog::open(&dir).unwrap();
+        assert_eq!(reopened.size(), 8);
+        assert_eq!(reopened.root_hash(8).unwrap(), log.root_hash(8).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn inclusion_and_consistency_proofs() {
+        let (log, dir) = test_log();
+
+        for tree_size in 1..=8 {
+            let root = log.root_hash(tree_size).unwrap();
+            for index in 0..tree_size {
+                let leaf = log.leaves[index as usize];
+                let proof = log.inclusion_proof(index, tree_size).unwrap();
+                assert!(verify_inclusion(&leaf, index, tree_size, &proof, &root));
+                assert!(!verify_inclusion(&leaf, (index + 1) % tree_size.max(2), tree_size.max(2), &proof, &root));
+            }
+
+            for old_size in 0..=tree_size {
+                let old_root = log.root_hash(old_size).unwrap();
+                let proof = log.consistency_proof(old_size, tree_size).unwrap();
+                assert!(verify_consistency(old_size, tree_size, &old_root, &root, &proof));
+                if old_size > 0 && old_size < tree_size {
+                    let wrong_root = log.root_hash(old_size - 1).unwrap();
+                    assert!(!verify_consistency(old_size, tree_size, &wrong_root, &root, &proof));
+                }
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn signed_tree_heads() {
+        let (log, dir) = test_log();
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut tree_head = log.sign_tree_head(&signing_key).unwrap();
+        assert_eq!(tree_head.tree_size, 8);
+        assert!(tree_head.verify(&signing_key.verifying_key()));
+
+        // Any change to the head invalidates the signature
+        tree_head.tree_size = 7;
+        assert!(!tree_head.verify(&signing_key.verifying_key()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_directory_is_not_created() {
+        let dir = std::env::temp_dir().join(format!("rust-cryptocraphy-log-missing-{}", std::process::id()));
+        assert!(TransparencyLog::open(&dir).is_err());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn partial_last_record_is_cut_off() {
+        let (log, dir) = test_log();
+        let entries_path = dir.join("entries");
+
+        // Append interrupted after the length and half the data
+        let mut file = OpenOptions::new().append(true).open(&entries_path).unwrap();
+        file.write_all(&[0, 0, 0, 8, 1, 2, 3, 4]).unwrap();
+        drop(file);
+
+        let mut reopened = TransparencyLog::open(&dir).unwrap();
+        assert_eq!(reopened.size(), 8);
+        assert_eq!(reopened.root_hash(8).unwrap(), log.root_hash(8).unwrap());
+
+        assert_eq!(reopened.append(b"after the crash").unwrap(), 8);
+        let reopened = TransparencyLog::open(&dir).unwrap();
+        assert_eq!(reopened.size(), 9);
+        assert_eq!(reopened.leaf(8).unwrap(), leaf_hash(b"after the crash"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_appends_get_distinct_indexes() {
+        let (_, dir) = test_log();
+
+        // Handles opened before the other one appended
+        let mut first = TransparencyLog::open(&dir).unwrap();
+        let mut second = TransparencyLog::open(&dir).unwrap();
+        assert_eq!(first.append(b"first").unwrap(), 8);
+        assert_eq!(second.append(b"second").unwrap(), 9);
+        assert_eq!(second.leaf(8).unwrap(), leaf_hash(b"first"));
+
+        let threads: Vec<_> = (0..4)
+            .map(|t| {
+                let dir = dir.clone();
+                std::thread::spawn(move || {
+                    let mut log = TransparencyLog::open(&dir).unwrap();
+                    (0..10).map(|i| log.append(format!("{}-{}", t, i).as_bytes()).unwrap()).collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut indexes: Vec<u64> = threads.into_iter().flat_map(|thread| thread.join().unwrap()).collect();
+        indexes.sort();
+        assert_eq!(indexes, (10..50).collect::<Vec<_>>());
+
+        let reopened = TransparencyLog::open(&dir).unwrap();
+        assert_eq!(reopened.size(), 50);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
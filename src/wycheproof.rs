@@ -0,0 +1,366 @@
+// ====================================================
+// WYCHEPROOF EDGE-CASE TESTS
+// ====================================================
+// The known-answer tests (kat.rs) only check that correct inputs give the
+// correct outputs. Project Wycheproof test suites also contain hundreds of
+// malformed or malicious inputs that must be rejected: modified tags,
+// truncated ciphertexts, high-S and non-DER ECDSA signatures, points not
+// on the curve, ... Each test is "valid", "invalid" or "acceptable"
+// (either answer is fine, e.g. legacy encodings), and has flags naming
+// the kind of edge case (ModifiedTag, SignatureMalleability, ...).
+// Results are reported per test group and per flag.
+//
+// The JSON suites are vendored in test_vectors/wycheproof and read at run
+// time, so suites can be added by dropping files in the directory. The
+// schema field of each file selects the check:
+// - aead_test_schema.json: AES-GCM and ChaCha20-Poly1305
+// - ecdsa_bitcoin_verify_schema.json: secp256k1 ECDSA with low-S signatures
+// - eddsa_verify_schema.json: Ed25519, with verify_strict (no small-order
+//   keys or non-canonical encodings, as the malleability tests require)
+//
+// The AEADs of this tool only take 256-bit keys, 96-bit nonces and
+// 128-bit tags: groups with other key or tag sizes are skipped, and so are
+// valid tests with other nonce sizes. Invalid ones must still be rejected.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use ed25519_dalek::{Signature as Ed25519Signature, VerifyingKey};
+use secp256k1::ecdsa::Signature as EcdsaSignature;
+use secp256k1::{Message as Secp256k1Message, PublicKey, Secp256k1};
+use serde::Deserialize;
+
+use crate::session::AeadSession;
+use crate::{hash_sha256, Cipher};
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Expected {
+    Valid,
+    Invalid,
+    Acceptable,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TestFile {
+    algorithm: String,
+    schema: String,
+    test_groups: Vec<serde_json::Value>, // Parsed according to the schema
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AeadGroup {
+    #[serde(rename = "type")]
+    kind: String,
+    key_size: u32,
+    iv_size: u32,
+    tag_size: u32,
+    tests: Vec<AeadTest>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AeadTest {
+    tc_id: u32,
+    comment: String,
+    key: String,
+    iv: String,
+    aad: String,
+    msg: String,
+    ct: String,
+    tag: String,
+    result: Expected,
+    #[serde(default)]
+    flags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignatureGroup {
+    #[serde(rename = "type")]
+    kind: String,
+    public_key: SignaturePublicKey,
+    tests: Vec<SignatureTest>,
+}
+
+// "uncompressed" for ECDSA, "pk" for EdDSA
+#[derive(Deserialize)]
+struct SignaturePublicKey {
+    uncompressed: Option<String>,
+    pk: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignatureTest {
+    tc_id: u32,
+    comment: String,
+    msg: String,
+    sig: String,
+    result: Expected,
+    #[serde(default)]
+    flags: Vec<String>,
+}
+
+// Test counts for one flag
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct FlagReport {
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+// Results of one test group
+pub struct GroupReport {
+    pub description: String,
+    pub passed: usize,
+    pub skipped: usize,
+    pub failures: Vec<String>, // "tcId N (comment): reason"
+    pub flags: BTreeMap<String, FlagReport>,
+}
+
+impl GroupReport {
+    fn new(description: String) -> Self {
+        GroupReport { description, passed: 0, skipped: 0, failures: Vec::new(), flags: BTreeMap::new() }
+    }
+
+    fn skip(&mut self, flags: &[String]) {
+        self.skipped += 1;
+        for flag in flags {
+            self.flags.entry(flag.clone()).or_default().skipped += 1;
+        }
+    }
+
+    fn record(&mut self, tc_id: u32, comment: &str, flags: &[String], expected: Expected, outcome: Accepted) {
+        let failure = match (expected, outcome) {
+            (_, Err(reason)) => Some(reason),
+            (Expected::Valid, Ok(false)) => Some("valid input rejected".to_string()),
+            (Expected::Invalid, Ok(true)) => Some("invalid input accepted".to_string()),
+            _ => None,
+        };
+        for flag in flags {
+            let counts = self.flags.entry(flag.clone()).or_default();
+            if failure.is_some() { counts.failed += 1 } else { counts.passed += 1 }
+        }
+        match failure {
+            Some(reason) => self.failures.push(format!("tcId {} ({}): {}", tc_id, comment, reason)),
+            None => self.passed += 1,
+        }
+    }
+}
+
+pub struct FileReport {
+    pub file: String,
+    pub algorithm: String,
+    pub groups: Vec<GroupReport>,
+}
+
+impl FileReport {
+    // Counts of every flag over all groups
+    pub fn flags(&self) -> BTreeMap<String, FlagReport> {
+        let mut totals: BTreeMap<String, FlagReport> = BTreeMap::new();
+        for (flag, counts) in self.groups.iter().flat_map(|group| &group.flags) {
+            let total = totals.entry(flag.clone()).or_default();
+            total.passed += counts.passed;
+            total.failed += counts.failed;
+            total.skipped += counts.skipped;
+        }
+        totals
+    }
+}
+
+// Outcome of one test: whether the input was accepted, or why it couldn't be run
+type Accepted = Result<bool, String>;
+
+fn aead_test(cipher: Cipher, test: &AeadTest) -> Result<Accepted, Box<dyn Error>> {
+    let (key, nonce, aad) = (hex::decode(&test.key)?, hex::decode(&test.iv)?, hex::decode(&test.aad)?);
+    let plaintext = hex::decode(&test.msg)?;
+    let mut ciphertext = hex::decode(&test.ct)?;
+    ciphertext.extend(hex::decode(&test.tag)?);
+
+    // decrypt_aes_gcm and decrypt_chacha20poly1305 have no associated data
+    // and expect 96-bit nonces
+    let decrypted = if aad.is_empty() && nonce.len() == 12 {
+        match cipher {
+            Cipher::AesGcm => crate::decrypt_aes_gcm(&ciphertext, &key, &nonce).ok(),
+            Cipher::ChaCha20Poly1305 => crate::decrypt_chacha20poly1305(&ciphertext, &key, &nonce).ok(),
+        }
+    } else {
+        AeadSession::new(cipher, &key)?.decrypt_with_aad(&ciphertext, &aad, &nonce).ok()
+    };
+
+    let Some(decrypted) = decrypted else {
+        return Ok(Ok(false));
+    };
+    if decrypted != plaintext {
+        return Ok(Err("decrypted to the wrong plaintext".to_string()));
+    }
+    // A valid test must also encrypt to the same ciphertext
+    if AeadSession::new(cipher, &key)?.encrypt_with_aad(&plaintext, &aad, &nonce)? != ciphertext {
+        return Ok(Err("encrypted to another ciphertext".to_string()));
+    }
+    Ok(Ok(true))
+}
+
+fn run_aead_group(cipher: Cipher, group: &serde_json::Value) -> Result<GroupReport, Box<dyn Error>> {
+    let group = AeadGroup::deserialize(group)?;
+    let mut report = GroupReport::new(format!("{} keySize {}, ivSize {}, tagSize {}", group.kind, group.key_size, group.iv_size, group.tag_size));
+
+    for test in &group.tests {
+        // Unsupported sizes can't be checked, except that invalid inputs must be rejected
+        let supported = (group.key_size, group.tag_size) == (256, 128);
+        if !supported || (group.iv_size != 96 && test.result != Expected::Invalid) {
+            report.skip(&test.flags);
+            continue;
+        }
+        let outcome = aead_test(cipher, test)?;
+        report.record(test.tc_id, &test.comment, &test.flags, test.result, outcome);
+    }
+    Ok(report)
+}
+
+// Bitcoin's ECDSA: the message is hashed with SHA-256, signatures are strict
+// DER with a low S (no malleability), as in secp256k1_example
+fn ecdsa_accepts(public_key: &PublicKey, test: &SignatureTest) -> Result<bool, Box<dyn Error>> {
+    let secp = Secp256k1::verification_only();
+    let message = Secp256k1Message::from_digest_slice(&hash_sha256(&hex::decode(&test.msg)?))?;
+    Ok(match EcdsaSignature::from_der(&hex::decode(&test.sig)?) {
+        Ok(signature) => secp.verify_ecdsa(&message, &signature, public_key).is_ok(),
+        Err(_) => false,
+    })
+}
+
+// verify_strict also rejects small-order public keys and R points, which
+// plain verify accepts (several signatures would then be valid)
+fn ed25519_accepts(public_key: &VerifyingKey, test: &SignatureTest) -> Result<bool, Box<dyn Error>> {
+    let message = hex::decode(&test.msg)?;
+    Ok(match Ed25519Signature::from_slice(&hex::decode(&test.sig)?) {
+        Ok(signature) => public_key.verify_strict(&message, &signature).is_ok(),
+        Err(_) => false,
+    })
+}
+
+fn run_signature_group(schema: &str, group: &serde_json::Value) -> Result<GroupReport, Box<dyn Error>> {
+    let group = SignatureGroup::deserialize(group)?;
+    let key_hex = group.public_key.uncompressed.as_ref().or(group.public_key.pk.as_ref()).ok_or("test group has no public key")?;
+    let mut report = GroupReport::new(format!("{} public key {}", group.kind, key_hex));
+    let key = hex::decode(key_hex)?;
+
+    for test in &group.tests {
+        // A public key the library refuses rejects every signature
+        let accepted = match schema {
+            "ecdsa_bitcoin_verify_schema.json" => match PublicKey::from_slice(&key) {
+                Ok(public_key) => ecdsa_accepts(&public_key, test)?,
+                Err(_) => false,
+            },
+            _ => match <[u8; 32]>::try_from(key.as_slice()).map(|key| VerifyingKey::from_bytes(&key)) {
+                Ok(Ok(public_key)) => ed25519_accepts(&public_key, test)?,
+                _ => false,
+            },
+        };
+        report.record(test.tc_id, &test.comment, &test.flags, test.result, Ok(accepted));
+    }
+    Ok(report)
+}
+
+// Runs one Wycheproof JSON file
+pub fn run_file(name: &str, json: &str) -> Result<FileReport, Box<dyn Error>> {
+    let file: TestFile = serde_json::from_str(json)?;
+    let mut groups = Vec::new();
+
+    for group in &file.test_groups {
+        let report = match (file.schema.as_str(), file.algorithm.as_str()) {
+            ("aead_test_schema.json", "AES-GCM") => run_aead_group(Cipher::AesGcm, group)?,
+            ("aead_test_schema.json", "CHACHA20-POLY1305") => run_aead_group(Cipher::ChaCha20Poly1305, group)?,
+            ("ecdsa_bitcoin_verify_schema.json", "ECDSA") | ("eddsa_verify_schema.json", "EDDSA") => run_signature_group(&file.schema, group)?,
+            (schema, algorithm) => return Err(format!("unsupported suite: {} with {}", algorithm, schema).into()),
+        };
+        groups.push(report);
+    }
+    Ok(FileReport { file: name.to_string(), algorithm: file.algorithm, groups })
+}
+
+// Runs every *.json file of a directory, sorted by name
+pub fn run_dir(dir: &Path) -> Result<Vec<FileReport>, Box<dyn Error>> {
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|path| path.extension().is_some_and(|extension| extension == "json"));
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| {
+            let name = path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+            run_file(&name, &fs::read_to_string(path)?).map_err(|e| format!("{}: {}", name, e).into())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vendored_suites_pass() {
+        let reports = run_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("test_vectors/wycheproof")).unwrap();
+        let files: Vec<&str> = reports.iter().map(|report| report.file.as_str()).collect();
+        assert_eq!(files, ["aes_gcm_test.json", "chacha20_poly1305_test.json", "ecdsa_secp256k1_sha256_bitcoin_test.json", "ed25519_test.json"]);
+
+        for report in &reports {
+            let passed: usize = report.groups.iter().map(|group| group.passed).sum();
+            assert!(passed > 0, "{}: nothing tested", report.file);
+            for group in &report.groups {
+                assert!(group.failures.is_empty(), "{} ({}): {:?}", report.file, group.description, group.failures);
+            }
+            assert!(report.flags().values().all(|counts| counts.failed == 0));
+        }
+
+        // Upstream group and flag structure: 45 AES-GCM groups, of which the
+        // 128 and 192-bit keys are skipped; every ChaCha20-Poly1305 test runs
+        let aes_gcm = &reports[0];
+        assert_eq!(aes_gcm.groups.len(), 45);
+        assert!(aes_gcm.groups.iter().any(|group| group.skipped > 0));
+        assert!(aes_gcm.flags()["ModifiedTag"].passed > 0);
+        let chacha = reports[1].flags();
+        assert!(chacha.values().all(|counts| counts.skipped == 0));
+        assert_eq!(chacha["InvalidNonceSize"].passed, 9);
+        let ed25519 = reports[3].flags();
+        assert!(ed25519["SignatureMalleability"].passed > 0);
+    }
+
+    // RFC 8032 TEST 2 in the EdDSA schema, with a modified signature and a modified message
+    #[test]
+    fn eddsa_schema() {
+        let json = r#"{
+            "algorithm": "EDDSA",
+            "schema": "eddsa_verify_schema.json",
+            "testGroups": [{
+                "type": "EddsaVerify",
+                "publicKey": { "pk": "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c" },
+                "tests": [
+                    { "tcId": 1, "comment": "", "flags": ["Ktv"], "msg": "72", "result": "valid",
+                      "sig": "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00" },
+                    { "tcId": 2, "comment": "modified signature", "flags": ["InvalidSignature"], "msg": "72", "result": "invalid",
+                      "sig": "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c01" },
+                    { "tcId": 3, "comment": "modified message", "flags": ["InvalidSignature"], "msg": "73", "result": "invalid",
+                      "sig": "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00" },
+                    { "tcId": 4, "comment": "truncated signature", "msg": "72", "result": "invalid", "sig": "92a0" }
+                ]
+            }]
+        }"#;
+        let report = run_file("eddsa_test.json", json).unwrap();
+        assert_eq!(report.groups[0].passed, 4);
+        assert!(report.groups[0].failures.is_empty());
+        assert_eq!(report.flags()["InvalidSignature"], FlagReport { passed: 2, failed: 0, skipped: 0 });
+        assert_eq!(report.flags().len(), 2); // tcId 4 has no flags
+
+        // A wrong expectation is reported with its test id
+        let report = run_file("eddsa_test.json", &json.replace(r#""result": "invalid", "sig": "92a0""#, r#""result": "valid", "sig": "92a0""#)).unwrap();
+        assert_eq!(report.groups[0].failures, ["tcId 4 (truncated signature): valid input rejected"]);
+    }
+}